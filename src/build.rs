@@ -1,24 +1,75 @@
+mod cache;
+mod category;
+mod feed;
 mod filter;
+mod history;
+mod linkcheck;
+mod native;
+mod search;
 mod sidebar;
+mod sitemap;
+mod tags;
+mod template;
+mod wikilink;
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
 use globwalk::GlobWalkerBuilder;
-use pandoc::{PandocOption, PandocOutput};
+use pandoc::PandocOption;
 use serde::Serialize;
 use serde_yaml::Mapping;
 use tempfile::NamedTempFile;
 
-use crate::config::DwwbConfig;
+use crate::config::{DwwbConfig, Renderer};
+use crate::error::{DwwbError, Result};
+use crate::report::Reporter;
 use crate::util::{path_to_url, title_case};
-use crate::{uw, Args};
+use crate::watch::LIVE_OUTPUT_DIR;
+use cache::BuildCache;
+use cache::InputHasher;
+pub(crate) use cache::CACHE_FILENAME;
 use filter::*;
-use sidebar::ArticleSidebarData;
+use native::NativeRenderer;
+use search::SEARCH_SCRIPT_FILENAME;
+pub use sidebar::ArticleSidebarData;
+use sidebar::{ArticleLink, SidebarTree};
+pub(crate) use template::Template;
+use wikilink::WikiLinks;
+use wikilink::WANTED_PAGES_ID;
+
+/// Options of a single build that are not a part of the project configuration
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// Additional script files to include into every article
+    ///
+    /// The paths are relative to the output root, but the files themselves aren't written by the build.
+    pub extra_script_files: Vec<String>,
+    /// Whether to ignore the build cache and convert every article
+    pub force: bool,
+    /// The number of articles to convert in parallel
+    ///
+    /// Overrides the `jobs` value of the configuration.
+    pub jobs: Option<usize>,
+    /// Whether to check the relative links and their fragments in the output directory after the build
+    ///
+    /// The broken links fail the build.
+    pub check_links: bool,
+    /// Whether to keep converting the other articles after an article fails
+    ///
+    /// All of the failures are reported at the end of the build.
+    pub keep_going: bool,
+}
 
 /// Performs the `build` command
-pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
+///
+/// Converts the articles of the project into html and copies over the other files.
+/// The progress is reported to the given reporter.
+pub fn build_project(cfg: DwwbConfig, reporter: &dyn Reporter, opts: &BuildOptions) -> Result<()> {
     cfg.outputs.ensure_dirs_exists()?;
 
     // gather the existing output files for checking what output files to delete
@@ -27,11 +78,10 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
         .build()
         .unwrap();
 
-    let mut output_files_to_delete =
-        Result::<HashSet<_>, _>::from_iter(output_file_walker.map(|entry| {
-            let entry = uw!(entry, "reading the old output files");
-            Ok(entry.into_path())
-        }))?;
+    let mut output_files_to_delete = Result::<HashSet<_>, _>::from_iter(
+        output_file_walker.map(|entry| entry.map(|entry| entry.into_path())),
+    )
+    .map_err(DwwbError::glob(cfg.outputs.root()))?;
 
     // copies the file, removing it from the set of files that will be removed
     let mut copy = |from: &Path, to: &Path| {
@@ -39,33 +89,20 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
         fs::copy(from, to)
     };
 
-    uw!(
-        copy(
-            cfg.inputs.style(),
-            &cfg.outputs.root().join(cfg.outputs.style())
-        ),
-        format!("copying the style sheet '{}'", cfg.inputs.style().display())
-    );
-
-    // pattern walkers for just the articles
-    let article_walker = uw!(
-        cfg.inputs.articles_glob().to_glob_walker_builder().build(),
-        "parsing the articles glob"
-    );
+    copy(
+        cfg.inputs.style(),
+        &cfg.outputs.root().join(cfg.outputs.style()),
+    )
+    .map_err(DwwbError::io("copying the style sheet", cfg.inputs.style()))?;
 
     // pattern walkers for files that need to be just copied
-    let copy_walkers = Result::<BTreeMap<_, _>, _>::from_iter(
+    let copy_walkers = Result::<BTreeMap<_, _>>::from_iter(
         cfg.inputs.non_articles_glob_iter().map(|(key, glob)| {
-            Ok((
-                key,
-                (
-                    &glob.base,
-                    uw!(
-                        glob.to_glob_walker_builder().build(),
-                        format!("parsing the {key} glob")
-                    ),
-                ),
-            ))
+            let walker = glob
+                .to_glob_walker_builder()
+                .build()
+                .map_err(DwwbError::glob(&glob.base))?;
+            Ok((key, (&glob.base, walker)))
         }),
     )?;
 
@@ -75,7 +112,7 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
     // just copy all the other files
     for (name, (base_dir, walker)) in copy_walkers {
         for file_res in walker {
-            let entry = uw!(file_res, "traversing the input directory");
+            let entry = file_res.map_err(DwwbError::glob(base_dir))?;
 
             let from = entry.path();
 
@@ -88,14 +125,9 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
                 .join(from.strip_prefix(base_dir).unwrap());
             let to = cfg.outputs.root().join(&to_base);
 
-            uw!(
-                fs::create_dir_all(to.parent().unwrap()),
-                "creating directories"
-            );
-            uw!(
-                copy(from, &to),
-                format!("copying '{}' file", from.display())
-            );
+            let to_dir = to.parent().unwrap();
+            fs::create_dir_all(to_dir).map_err(DwwbError::io("creating the directory", to_dir))?;
+            copy(from, &to).map_err(DwwbError::io("copying the file", from))?;
 
             if name == "scripts" {
                 script_files.push(path_to_url(to_base));
@@ -103,30 +135,276 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
         }
     }
 
+    // the search page needs the search script, which the older projects don't have
+    if let Some((url, path)) = search::write_default_search_script(&cfg, &script_files)? {
+        reporter.message(&format!(
+            "---\nThe project has no '{}' script, so the built-in search script is used",
+            cfg.inputs
+                .scripts_dir()
+                .join(search::SEARCH_JS_FILENAME)
+                .display()
+        ));
+        output_files_to_delete.remove(&path);
+        script_files.push(url);
+    }
+
+    script_files.extend(opts.extra_script_files.iter().cloned());
+
+    // the errors of the articles that failed to build in the `keep_going` mode
+    let mut failures = Vec::new();
+
+    let articles = read_article_tree(&cfg, opts.keep_going, &mut failures)?;
+    for article in articles.root.iter() {
+        if let Some(html_path) = &article.html_file_path {
+            output_files_to_delete.remove(html_path);
+        }
+    }
+    let search_url = articles
+        .root
+        .get(search::SEARCH_ID)
+        .map(|page| page.link_url.clone())
+        .unwrap_or_default();
+
+    for path in search::write_search_index(&cfg, &articles.root, &articles.links)? {
+        output_files_to_delete.remove(&path);
+    }
+    for path in sitemap::write_sitemap(&cfg, &articles.root)? {
+        output_files_to_delete.remove(&path);
+    }
+    for path in feed::write_feeds(&cfg, &articles.root, &articles.links)? {
+        output_files_to_delete.remove(&path);
+    }
+
+    /// Helper function to change things into key/value pairs
+    fn val_pair<T: Into<serde_yaml::Value>, U: Serialize>(
+        name: T,
+        value: U,
+    ) -> (serde_yaml::Value, serde_yaml::Value) {
+        (name.into(), serde_yaml::to_value(value).unwrap())
+    }
+
+    let mut variables = Mapping::from_iter([
+        val_pair("articles-title", &cfg.articles_title),
+        val_pair("sub-articles-title", &cfg.sub_articles_title),
+        val_pair("toc-title", &cfg.toc_title),
+        val_pair("backlinks-title", &cfg.backlinks_title),
+        val_pair("search-title", &cfg.search_title),
+        val_pair("script-file", script_files),
+        val_pair("search-url", search_url),
+        val_pair("search-index", SEARCH_SCRIPT_FILENAME),
+    ]);
+    if let Some(feed) = &cfg.feed {
+        variables.extend([
+            val_pair("feed-title", feed.title.as_ref().unwrap_or(&cfg.name)),
+            val_pair("feed-url", feed::ATOM_FILENAME),
+        ]);
+        if feed.rss {
+            variables.extend([val_pair("rss-url", feed::RSS_FILENAME)]);
+        }
+    }
+    let mut defaults_data = Mapping::new();
+    defaults_data.insert("variables".into(), variables.clone().into());
+
+    // the defaults file must exist until all of the articles have been converted
+    let mut defaults_file = None;
+    let renderer = match cfg.renderer {
+        Renderer::Pandoc => {
+            let file = NamedTempFile::new().map_err(DwwbError::io(
+                "creating the defaults file",
+                std::env::temp_dir(),
+            ))?;
+            serde_yaml::to_writer(&file, &defaults_data).map_err(|source| DwwbError::Yaml {
+                path: file.path().to_path_buf(),
+                source,
+            })?;
+
+            let mut pandoc_options = {
+                use PandocOption::*;
+                vec![
+                    Defaults(file.path().to_path_buf()),
+                    Template(cfg.inputs.article_template().to_path_buf()),
+                    Css(path_to_url(cfg.outputs.style())),
+                    Standalone,
+                    TableOfContents,
+                    TableOfContentsDepth(cfg.toc_depth),
+                ]
+            };
+            if let Some(renderer) = &cfg.math_renderer {
+                pandoc_options.push(renderer.to_pandoc_option())
+            }
+
+            defaults_file = Some(file);
+            ArticleRenderer::Pandoc(pandoc_options)
+        }
+        Renderer::Native => ArticleRenderer::Native(NativeRenderer::new(&cfg, variables)?),
+    };
+
+    let mut cache = BuildCache::load(opts.force);
+    let shared_inputs_hash = cache::hash_shared_inputs(&cfg, &defaults_data)?;
+
+    reporter.message(match cfg.renderer {
+        Renderer::Pandoc => "Processing articles with pandoc...",
+        Renderer::Native => "Processing articles...",
+    });
+    let write_result = write_articles(
+        &cfg,
+        reporter,
+        opts,
+        &renderer,
+        &articles,
+        &mut cache,
+        &shared_inputs_hash,
+    );
+    // save the cache even on failure, so that the successfully written articles are not rebuilt
+    if let Err(e) = cache.save(cfg.outputs.root()) {
+        reporter.warning(&e);
+    }
+    drop(defaults_file);
+    let WriteSummary {
+        processed,
+        skipped,
+        failures: write_failures,
+    } = write_result?;
+    failures.extend(write_failures);
+    if skipped > 0 {
+        reporter.message(&format!(
+            "---\n{processed} files processed, {skipped} unchanged files skipped."
+        ));
+    } else {
+        reporter.message(&format!("---\n{processed} files processed."));
+    }
+
+    if !output_files_to_delete.is_empty() {
+        reporter.message(&format!(
+            "---\nDeleting {} old file(s)...",
+            output_files_to_delete.len()
+        ));
+        for path in output_files_to_delete {
+            if let Err(e) = fs::remove_file(&path) {
+                reporter.warning(&DwwbError::io("deleting the file", &path)(e));
+            }
+            reporter.message(&format!("Deleted '{}'", path.display()));
+        }
+    }
+
+    // remove all empty directories
+    let dir_walker = GlobWalkerBuilder::new(cfg.outputs.root(), "**")
+        .file_type(globwalk::FileType::DIR)
+        .contents_first(true)
+        .build()
+        .unwrap();
+
+    for entry in dir_walker.flatten() {
+        // this function removes only empty directories
+        if fs::remove_dir(entry.path()).is_ok() {
+            reporter.message(&format!(
+                "Deleted the empty directory '{}'",
+                entry.path().display()
+            ));
+        }
+    }
+
+    if !failures.is_empty() {
+        // the workers finish in an arbitrary order
+        failures.sort_by(|a, b| a.path().cmp(&b.path()));
+        return Err(DwwbError::ArticlesFailed(failures));
+    }
+
+    if opts.check_links {
+        reporter.message("---\nChecking the links...");
+        // the extra scripts are provided by whoever serves the output, like the live reload server
+        let broken = linkcheck::check_links(cfg.outputs.root(), &opts.extra_script_files)?;
+        if !broken.is_empty() {
+            return Err(DwwbError::LinksBroken(broken));
+        }
+    }
+
+    reporter.message("---\nAll done");
+    Ok(())
+}
+
+/// The tree of the articles of the project with the targets of their wiki links
+pub(crate) struct ArticleTree {
+    /// The index article, which has all the other articles as its sub-articles
+    pub root: ArticleSidebarData,
+    /// The targets of the wiki links, which include the generated pages
+    pub links: WikiLinks,
+}
+
+/// Reads the articles of the project into a tree, and adds the generated pages to it
+///
+/// The index article is the root of the tree.
+/// In the `keep_going` mode, the articles that can't be read and the generated pages
+/// that can't be generated are left out, and their errors are added to the failures.
+/// Otherwise the first error is returned.
+pub(crate) fn read_article_tree(
+    cfg: &DwwbConfig,
+    keep_going: bool,
+    failures: &mut Vec<DwwbError>,
+) -> Result<ArticleTree> {
+    let mut fail = |e: DwwbError| {
+        if keep_going {
+            failures.push(e);
+            Ok(())
+        } else {
+            Err(e)
+        }
+    };
+
+    let articles_glob = cfg.inputs.articles_glob();
+    let article_walker = articles_glob
+        .to_glob_walker_builder()
+        .build()
+        .map_err(DwwbError::glob(&articles_glob.base))?;
+
     // a map from the parent path to its articles' sidebar related data
     let mut dirs_to_sb_data = HashMap::<PathBuf, Vec<ArticleSidebarData>>::new();
     // the tree version of the above map
-    // uses the index file as the root
-    let mut articles_root = ArticleSidebarData::from_article_meta(&cfg, cfg.inputs.index())?;
-    output_files_to_delete.remove(articles_root.html_file_path.as_ref().unwrap());
+    // uses the index file as the root, or an empty root without a page of its own if the index can't be read
+    let index = cfg.inputs.index();
+    let mut articles_root = match ArticleSidebarData::from_article_meta(cfg, index) {
+        Ok(root) => root,
+        Err(e) => {
+            fail(e)?;
+            let id = ArticleSidebarData::id_from_path(cfg, index);
+            ArticleSidebarData {
+                title: id.clone(),
+                id,
+                ..Default::default()
+            }
+        }
+    };
 
-    // construct the map
+    // the articles are read in the order of their paths rather than the order of the file system,
+    // so that the article that is kept out of the conflicting ones is the same on every build
+    let mut article_paths = Vec::new();
     for article_res in article_walker {
-        let entry = uw!(article_res, "traversing the article directory");
-        let data = read_md_article(&cfg, entry.path(), &mut dirs_to_sb_data)?;
-        output_files_to_delete.remove(data.html_file_path.as_ref().unwrap());
+        let entry = article_res.map_err(DwwbError::glob(&articles_glob.base))?;
+        article_paths.push(entry.into_path());
+    }
+    article_paths.sort();
+
+    // construct the map
+    for path in &article_paths {
+        if let Err(e) = read_md_article(cfg, path, &mut dirs_to_sb_data) {
+            fail(e)?;
+        }
     }
 
     // transform the sidebar data map into a tree
     for (path, meta_vec) in dirs_to_sb_data.drain() {
         // traverse the hierarchy to the correct node to add the leaves
         let mut meta_it = &mut articles_root;
+        let mut dir_path = PathBuf::new();
         for dir in path.components() {
+            dir_path.push(dir);
             let dir = dir.as_os_str().to_string_lossy().to_string();
+            // the directories have the same ids as the articles they belong to
+            let id = path_to_url(&dir_path);
 
             // advance iterator without upsetting compiler
             let new_idx = if let Some(new_meta) =
-                meta_it.sub_articles.iter().position(|meta| meta.id == dir)
+                meta_it.sub_articles.iter().position(|meta| meta.id == id)
             {
                 new_meta
             } else {
@@ -135,7 +413,7 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
                 // create default metadata for the category
                 let idx = meta_it.sub_articles.len();
                 meta_it.sub_articles.push(ArticleSidebarData {
-                    id: dir.clone(),
+                    id,
                     title,
                     ..Default::default()
                 });
@@ -148,6 +426,19 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
         for meta in meta_vec {
             // check if the metadata for this already exists
             if let Some(existing_meta) = meta_it.get_mut(&meta.id) {
+                // two articles with the same id, like `foo.md` and `foo.markdown`
+                if let Some(existing_path) = &existing_meta.md_file_path {
+                    let mut paths = [existing_path.clone(), meta.md_file_path.unwrap_or_default()];
+                    paths.sort();
+                    let [path, other] = paths;
+                    fail(DwwbError::IdConflict {
+                        path,
+                        other,
+                        id: meta.id,
+                    })?;
+                    continue;
+                }
+
                 // update the existing while keeping the sub articles
                 let old_sub = existing_meta.sub_articles.clone();
                 *existing_meta = meta;
@@ -159,199 +450,395 @@ pub fn build_project(cfg: DwwbConfig, args: Args) -> Result<(), String> {
         }
     }
 
-    /// Helper function to change things into key/value pairs
-    fn val_pair<T: Into<serde_yaml::Value>, U: Serialize>(
-        name: T,
-        value: U,
-    ) -> (serde_yaml::Value, serde_yaml::Value) {
-        (name.into(), serde_yaml::to_value(value).unwrap())
-    }
+    articles_root.sort_recursive(cfg.sidebar_sort);
 
-    let mut defaults_data = Mapping::new();
-    defaults_data.insert(
-        "variables".into(),
-        Mapping::from_iter([
-            val_pair("articles-title", &cfg.articles_title),
-            val_pair("sub-articles-title", &cfg.sub_articles_title),
-            val_pair("toc-title", &cfg.toc_title),
-            val_pair("sidebar-data", &articles_root),
-            val_pair("script-file", script_files),
-        ])
-        .into(),
+    // generate the listing pages of the categories without an article of their own
+    let category_template = Template::load_or_default(
+        cfg.inputs.category_template(),
+        include_str!("include/templates/category.md"),
     );
+    if let Err(e) = category_template
+        .and_then(|template| category::generate_category_pages(cfg, &mut articles_root, &template))
+    {
+        fail(e)?;
+    }
 
-    let defaults_file = uw!(NamedTempFile::new(), "creating the defaults file");
-    uw!(
-        serde_yaml::to_writer(&defaults_file, &defaults_data),
-        "serializing the defaults file"
-    );
+    // generate the tags section from the keywords of the articles
+    if let Err(e) = tags::generate_tag_pages(cfg, &mut articles_root) {
+        fail(e)?;
+    }
 
-    let mut pandoc_options = {
-        use PandocOption::*;
-        vec![
-            Defaults(defaults_file.path().to_path_buf()),
-            Template(cfg.inputs.article_template().to_path_buf()),
-            Css(path_to_url(cfg.outputs.style())),
-            Standalone,
-            TableOfContents,
-            TableOfContentsDepth(cfg.toc_depth),
-        ]
-    };
-    if let Some(renderer) = &cfg.math_renderer {
-        pandoc_options.push(renderer.to_pandoc_option())
+    // generate the search page for the search script
+    if let Err(e) = search::generate_search_page(cfg, &mut articles_root) {
+        fail(e)?;
     }
 
-    args.msg("Processing articles with pandoc...");
-    let outputs = uw!(
-        pandoc_write(&cfg, &args, &pandoc_options, &articles_root),
-        "writing articles with pandoc"
-    );
-    args.msg(format!("---\n{} files processed.", outputs.len()));
+    // read the git history of the articles, and list the latest commits on the recent changes page
+    if let Some(commits) = history::read_history(cfg) {
+        history::set_file_histories(&mut articles_root, &commits);
+        if let Err(e) = history::generate_recent_changes_page(cfg, &mut articles_root, &commits) {
+            fail(e)?;
+        }
+    }
 
-    if !output_files_to_delete.is_empty() {
-        args.msg(format!(
-            "---\nDeleting {} old file(s)...",
-            output_files_to_delete.len()
-        ));
-        for path in output_files_to_delete {
-            if let Err(e) = fs::remove_file(&path) {
-                eprintln!("Error while deleting the file '{}': {e}", path.display());
+    // generate the wanted pages article if any of the links point to missing articles
+    let mut links = WikiLinks::new(cfg, &articles_root);
+    let wanted = wikilink::find_wanted_pages(&links, &articles_root);
+    if !wanted.is_empty() {
+        let mut page = ArticleSidebarData {
+            id: WANTED_PAGES_ID.to_string(),
+            title: cfg.wanted_pages_title.clone(),
+            ..Default::default()
+        };
+        page.set_generated(
+            cfg,
+            wikilink::wanted_pages_source(&cfg.wanted_pages_title, &wanted),
+        );
+        match articles_root.check_generated_id(cfg, &page) {
+            Ok(()) => {
+                links.insert(&page);
+                articles_root.sub_articles.push(page);
             }
-            args.msg(format!("Deleted '{}'", path.display()));
+            Err(e) => fail(e)?,
         }
     }
 
-    // remove all empty directories
-    let dir_walker = GlobWalkerBuilder::new(cfg.outputs.root(), "**")
-        .file_type(globwalk::FileType::DIR)
-        .contents_first(true)
-        .build()
-        .unwrap();
+    // the generated articles must be sorted in too
+    articles_root.sort_recursive(cfg.sidebar_sort);
+    Ok(ArticleTree {
+        root: articles_root,
+        links,
+    })
+}
 
-    for entry in dir_walker.flatten() {
-        // this function removes only empty directories
-        if fs::remove_dir(entry.path()).is_ok() {
-            args.msg(format!(
-                "Deleted the empty directory '{}'",
-                entry.path().display()
-            ));
+/// Performs the `clean` command
+///
+/// Removes the output directory, the output directory of the live reload builds, and the build cache.
+pub fn clean_project(cfg: &DwwbConfig, reporter: &dyn Reporter) -> Result<()> {
+    for (dir, action) in [
+        (cfg.outputs.root(), "removing the output directory"),
+        (
+            Path::new(LIVE_OUTPUT_DIR),
+            "removing the live reload output directory",
+        ),
+    ] {
+        if dir.is_dir() {
+            reporter.message(&format!("Removing the directory '{}'...", dir.display()));
+            fs::remove_dir_all(dir).map_err(DwwbError::io(action, dir))?;
         }
     }
+    BuildCache::remove().map_err(DwwbError::io("removing the build cache", CACHE_FILENAME))?;
 
-    args.msg("---\nAll done");
+    reporter.message("All done");
     Ok(())
 }
 
-fn pandoc_write(
-    cfg: &DwwbConfig,
-    args: &Args,
-    options: &[PandocOption],
-    root: &ArticleSidebarData,
-) -> Result<Vec<PandocOutput>, String> {
-    // the depth of the articles output directory
-    let articles_root_depth = cfg
-        .outputs
-        .articles_dir()
-        .parent()
-        .map(|p| p.components().count())
-        .unwrap_or(0);
-
-    let mut outputs = Vec::new();
-    pandoc_write_recursive(
-        cfg,
-        args,
-        options,
-        root,
-        0,
-        articles_root_depth,
-        &mut outputs,
-    )?;
-    return Ok(outputs);
+/// A single article to be converted with pandoc
+struct ArticleJob<'a> {
+    /// The markdown file of the article, or the html file if the article is generated
+    md_path: &'a Path,
+    html_path: &'a Path,
+    /// The relative url from the article to the output root
+    root_url: String,
+    /// The sub-article indices from the root of the tree to the article
+    sidebar_path: Vec<usize>,
+    /// The pandoc defaults specific to this article
+    defaults_data: Mapping,
+    /// The markdown source of the article, either the input file or the generated source
+    source: &'a str,
+}
+
+impl ArticleJob<'_> {
+    /// Adds the tree of the articles with this article marked as the current one to the variables
+    ///
+    /// The tree is as large as the whole wiki, so it's added only when the article is about to be converted.
+    fn add_sidebar_data(&mut self, root: &ArticleSidebarData) {
+        let tree = SidebarTree {
+            root,
+            current: &self.sidebar_path,
+        };
+        if let Some(serde_yaml::Value::Mapping(variables)) = self.defaults_data.get_mut("variables")
+        {
+            variables.insert("sidebar-data".into(), serde_yaml::to_value(tree).unwrap());
+        }
+    }
+
+    /// Returns the hash of all the inputs of this article for the build cache
+    fn inputs_hash(&self, shared_inputs_hash: &str) -> Result<String> {
+        let mut hasher = InputHasher::new();
+        hasher
+            .update(shared_inputs_hash)
+            .update(self.source)
+            .update_yaml(&self.defaults_data)?
+            .update(&self.root_url);
+        Ok(hasher.finish())
+    }
+
+    /// Converts the article into html with the given renderer
+    ///
+    /// The wiki links of the article are resolved before the conversion.
+    fn execute(
+        self,
+        cfg: &DwwbConfig,
+        reporter: &dyn Reporter,
+        renderer: &ArticleRenderer,
+        links: &WikiLinks,
+    ) -> Result<()> {
+        let dir_path = self.html_path.parent().unwrap();
+        fs::create_dir_all(dir_path).map_err(DwwbError::io("creating the directory", dir_path))?;
+
+        let md_path = self.md_path;
+        let source = links.replace_links(md_path, self.source, &self.root_url)?;
+
+        match renderer {
+            ArticleRenderer::Pandoc(options) => {
+                self.execute_pandoc(cfg, reporter, options, source)?
+            }
+            ArticleRenderer::Native(native) => {
+                let variables = match self.defaults_data.get("variables") {
+                    Some(serde_yaml::Value::Mapping(variables)) => variables.clone(),
+                    _ => Mapping::new(),
+                };
+                native.render_article(
+                    md_path,
+                    source,
+                    self.html_path,
+                    &self.root_url,
+                    &variables,
+                )?
+            }
+        }
+
+        reporter.message(&format!("Processed \"{}\"", md_path.display()));
+        Ok(())
+    }
 
-    fn pandoc_write_recursive(
+    /// Converts the given markdown source of the article with pandoc
+    fn execute_pandoc(
+        self,
         cfg: &DwwbConfig,
-        args: &Args,
+        reporter: &dyn Reporter,
         options: &[PandocOption],
-        node: &ArticleSidebarData,
-        depth: usize,
-        articles_root_depth: usize,
-        outputs: &mut Vec<PandocOutput>,
-    ) -> Result<(), String> {
-        if let Some(md_path) = &node.md_file_path {
-            let html_path = node.html_file_path.as_ref().unwrap();
-            let root_url = "../".repeat(depth.max(0) + articles_root_depth);
-
-            let mut defaults_data = Mapping::new();
-            if !node.sub_articles.is_empty() && depth > 0 {
-                defaults_data.insert(
-                    "variables".into(),
-                    Mapping::from_iter([(
-                        "current-sub-articles".into(),
-                        serde_yaml::to_value(&node.sub_articles).unwrap(),
-                    )])
-                    .into(),
-                );
+        source: String,
+    ) -> Result<()> {
+        let Self {
+            md_path,
+            html_path,
+            root_url,
+            defaults_data,
+            ..
+        } = self;
+
+        let article_defaults = NamedTempFile::new().map_err(DwwbError::io(
+            "creating the defaults file",
+            std::env::temp_dir(),
+        ))?;
+        serde_yaml::to_writer(&article_defaults, &defaults_data).map_err(|source| {
+            DwwbError::Yaml {
+                path: article_defaults.path().to_path_buf(),
+                source,
             }
+        })?;
 
-            let article_defaults = NamedTempFile::new().map_err(|e| {
-                format!(
-                    "Failed to create the defaults file for the article '{}' ('{}'): {e}",
-                    node.title,
-                    md_path.display()
-                )
-            })?;
-            serde_yaml::to_writer(&article_defaults, &defaults_data).map_err(|e| {
-                format!(
-                    "Failed to serialize the defaults file for the article '{}' ('{}'): {e}",
-                    node.title,
-                    md_path.display()
-                )
-            })?;
+        let mut pd = pandoc::new();
+        pd.add_options(options)
+            .add_option(PandocOption::Defaults(
+                article_defaults.path().to_path_buf(),
+            ))
+            .set_variable("base-url", &root_url)
+            .set_input_format(pandoc::InputFormat::Markdown, Vec::new())
+            .set_input(pandoc::InputKind::Pipe(source))
+            .set_output(pandoc::OutputKind::File(html_path.to_path_buf()))
+            .add_filter(variable_replacer_filter(root_url))
+            .set_show_cmdline(cfg.debug_pandoc_cmd);
+
+        if cfg.debug_pandoc_cmd {
+            // make the process output clearer if the pandoc output is being output
+            reporter.message("---\n\n  Pandoc invocations:")
+        }
+        pd.execute().map_err(|source| DwwbError::Pandoc {
+            path: md_path.to_path_buf(),
+            source,
+        })?;
 
-            let mut pd = pandoc::new();
-            pd.add_options(options)
-                .add_option(PandocOption::Defaults(
-                    article_defaults.path().to_path_buf(),
-                ))
-                .set_variable("base-url", &root_url)
-                .add_input(&md_path)
-                .set_output(pandoc::OutputKind::File(html_path.to_path_buf()))
-                .add_filter(variable_replacer_filter(root_url))
-                .set_show_cmdline(cfg.debug_pandoc_cmd);
-
-            let dir_path = html_path.parent().unwrap();
-            fs::create_dir_all(dir_path).map_err(|e| {
-                format!(
-                    "Failed to create the directory '{}': {e}",
-                    dir_path.display()
-                )
-            })?;
+        if cfg.debug_pandoc_cmd {
+            reporter.message("");
+        }
+        Ok(())
+    }
+}
+
+/// The backend that converts the markdown articles into html
+enum ArticleRenderer {
+    /// Pandoc with the options shared by all of the articles
+    Pandoc(Vec<PandocOption>),
+    Native(NativeRenderer),
+}
 
-            if cfg.debug_pandoc_cmd {
-                // make the process output clearer if the pandoc output is being output
-                print!("---\n\n  Pandoc invocations:\n")
+/// The results of converting the articles
+struct WriteSummary {
+    /// The number of the converted articles
+    processed: usize,
+    /// The number of the unchanged articles that were skipped
+    skipped: usize,
+    /// The errors of the articles that failed to convert in the `keep_going` mode
+    failures: Vec<DwwbError>,
+}
+
+/// Converts all the articles in the tree, skipping the ones that are unchanged since the last build
+///
+/// The articles are converted in parallel on a pool of worker threads.
+/// Unless the `keep_going` option is set, stops at the first error
+/// after the articles that are already being converted are finished.
+fn write_articles(
+    cfg: &DwwbConfig,
+    reporter: &dyn Reporter,
+    opts: &BuildOptions,
+    renderer: &ArticleRenderer,
+    articles: &ArticleTree,
+    cache: &mut BuildCache,
+    shared_inputs_hash: &str,
+) -> Result<WriteSummary> {
+    // the pandoc command lines would get mixed up if they were printed from multiple threads
+    let workers = if cfg.debug_pandoc_cmd {
+        1
+    } else {
+        opts.jobs.or(cfg.jobs).unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(usize::from)
+                .unwrap_or(1)
+        })
+    };
+
+    let ArticleTree { root, links } = articles;
+    let backlinks = wikilink::find_backlinks(links, root);
+
+    let mut jobs = Vec::new();
+    collect_article_jobs(root, root, &mut Vec::new(), &backlinks, &mut jobs);
+
+    let queue = Mutex::new(jobs.into_iter());
+    let processed = AtomicUsize::new(0);
+    let skipped = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    let cache = Mutex::new(cache);
+
+    let worker = || loop {
+        if !opts.keep_going && !failures.lock().unwrap().is_empty() {
+            return;
+        }
+        let mut job = match queue.lock().unwrap().next() {
+            Some(job) => job,
+            None => return,
+        };
+        job.add_sidebar_data(root);
+
+        let html_path = job.html_path.to_path_buf();
+        let result = job.inputs_hash(shared_inputs_hash).and_then(|hash| {
+            if cache.lock().unwrap().is_fresh(&html_path, &hash) {
+                skipped.fetch_add(1, Ordering::Relaxed);
+            } else {
+                job.execute(cfg, reporter, renderer, links)?;
+                processed.fetch_add(1, Ordering::Relaxed);
             }
-            outputs.push(pd.execute().map_err(|e| format!("pandoc error: {e}"))?);
+            cache.lock().unwrap().insert(html_path, hash);
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            failures.lock().unwrap().push(e);
+        }
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(worker);
+        }
+    });
+
+    let mut failures = failures.into_inner().unwrap();
+    if !opts.keep_going && !failures.is_empty() {
+        return Err(failures.swap_remove(0));
+    }
 
-            if cfg.debug_pandoc_cmd {
-                args.msg("");
+    return Ok(WriteSummary {
+        processed: processed.into_inner(),
+        skipped: skipped.into_inner(),
+        failures,
+    });
+
+    /// Collects the jobs of the node and its sub-articles
+    ///
+    /// The path is the sub-article indices from the root to the node.
+    fn collect_article_jobs<'a>(
+        node: &'a ArticleSidebarData,
+        root: &ArticleSidebarData,
+        path: &mut Vec<usize>,
+        backlinks: &HashMap<String, Vec<ArticleLink>>,
+        jobs: &mut Vec<ArticleJob<'a>>,
+    ) {
+        // the generated articles have no input file, so the output file stands in for it
+        let md_path = match (&node.md_file_path, &node.generated_source) {
+            (Some(md_path), _) => Some(md_path),
+            (None, Some(_)) => node.html_file_path.as_ref(),
+            (None, None) => None,
+        };
+        if let (Some(md_path), Some(source)) = (md_path, node.markdown_source()) {
+            let mut variables = Mapping::from_iter([(
+                "breadcrumbs".into(),
+                serde_yaml::to_value(root.breadcrumbs(path)).unwrap(),
+            )]);
+            if !node.keyword_links.is_empty() {
+                variables.insert(
+                    "keyword-links".into(),
+                    serde_yaml::to_value(&node.keyword_links).unwrap(),
+                );
+            }
+            let (prev, next) = root.prev_and_next(path);
+            if let Some(prev) = prev {
+                variables.insert("prev-article".into(), serde_yaml::to_value(prev).unwrap());
+            }
+            if let Some(next) = next {
+                variables.insert("next-article".into(), serde_yaml::to_value(next).unwrap());
+            }
+            if !node.sub_articles.is_empty() && !path.is_empty() {
+                variables.insert(
+                    "current-sub-articles".into(),
+                    serde_yaml::to_value(&node.sub_articles).unwrap(),
+                );
+            }
+            if let Some(backlinks) = backlinks.get(url_escape::decode(&node.link_url).as_ref()) {
+                variables.insert("backlinks".into(), serde_yaml::to_value(backlinks).unwrap());
             }
-            args.msg(format!("Processed \"{}\"", md_path.display()));
+            if let Some(history) = &node.history {
+                variables.insert(
+                    "last-modified".into(),
+                    history.last_modified.format("%Y-%m-%d").to_string().into(),
+                );
+                variables.insert(
+                    "contributors".into(),
+                    serde_yaml::to_value(&history.contributors).unwrap(),
+                );
+            }
+
+            let defaults_data = Mapping::from_iter([("variables".into(), variables.into())]);
+
+            jobs.push(ArticleJob {
+                md_path,
+                html_path: node.html_file_path.as_ref().unwrap(),
+                // the generated pages can be deeper in the output directory than in the tree
+                root_url: "../".repeat(node.link_url.matches('/').count()),
+                sidebar_path: path.clone(),
+                defaults_data,
+                source,
+            });
         }
 
         // generate all of the child articles
-        for n in &node.sub_articles {
-            pandoc_write_recursive(
-                cfg,
-                args,
-                options,
-                n,
-                depth + 1,
-                articles_root_depth,
-                outputs,
-            )?;
+        for (idx, n) in node.sub_articles.iter().enumerate() {
+            path.push(idx);
+            collect_article_jobs(n, root, path, backlinks, jobs);
+            path.pop();
         }
-        Ok(())
     }
 }
 
@@ -360,11 +847,11 @@ fn pandoc_write(
 /// Uses the parent root as the key, with the articles directory prefix stripped off.
 ///
 /// Returns a reference to the generated data
-fn read_md_article<'a, 'b, 'c>(
-    cfg: &'a DwwbConfig,
-    path: &'b Path,
+fn read_md_article<'c>(
+    cfg: &DwwbConfig,
+    path: &Path,
     dirs_to_sidebar_data: &'c mut HashMap<PathBuf, Vec<ArticleSidebarData>>,
-) -> Result<&'c ArticleSidebarData, String> {
+) -> Result<&'c ArticleSidebarData> {
     let sb_data = ArticleSidebarData::from_article_meta(cfg, path)?;
     let parent = path.parent().map(ToOwned::to_owned).unwrap_or_default();
 
@@ -380,3 +867,38 @@ fn read_md_article<'a, 'b, 'c>(
 
     Ok(entry.last().unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job<'a>(md_path: &'a Path, source: &'a str) -> ArticleJob<'a> {
+        ArticleJob {
+            md_path,
+            html_path: Path::new("html/articles/alice.html"),
+            root_url: "../".to_string(),
+            sidebar_path: vec![0],
+            defaults_data: Mapping::new(),
+            source,
+        }
+    }
+
+    #[test]
+    fn changed_articles_change_the_hash() {
+        let md_path = Path::new("articles/alice.md");
+        let source = "---\ntitle: Alice\n---\n";
+        let hash = job(md_path, source).inputs_hash("shared").unwrap();
+        assert_eq!(job(md_path, source).inputs_hash("shared").unwrap(), hash);
+        assert_ne!(job(md_path, source).inputs_hash("changed").unwrap(), hash);
+        assert_ne!(
+            job(md_path, "---\ntitle: Alice\n---\n\nHello\n")
+                .inputs_hash("shared")
+                .unwrap(),
+            hash
+        );
+
+        let mut moved = job(md_path, source);
+        moved.root_url = "../../".to_string();
+        assert_ne!(moved.inputs_hash("shared").unwrap(), hash);
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use sha2::{Digest, Sha256};
+
+use crate::config::DwwbConfig;
+use crate::error::{DwwbError, Result};
+
+/// The name of the build cache file in the project directory
+pub const CACHE_FILENAME: &str = ".dwwb-cache";
+
+/// Keeps track of the hashes of the inputs that every output article was generated from
+///
+/// Used for skipping the articles whose inputs haven't changed since the previous build.
+#[derive(Debug, Clone, Default)]
+pub struct BuildCache {
+    /// The hashes from the previous build
+    previous: CacheData,
+    /// The hashes of the articles that have been written or skipped in this build
+    current: CacheData,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CacheData {
+    /// A map from the output html files to the hashes of their inputs
+    articles: BTreeMap<PathBuf, String>,
+}
+
+impl BuildCache {
+    /// Loads the cache of the previous build from the current working directory
+    ///
+    /// If the cache doesn't exist, it can't be read, or the build is forced,
+    /// every article will be considered changed.
+    pub fn load(force: bool) -> Self {
+        Self::load_file(Path::new(CACHE_FILENAME), force)
+    }
+
+    /// Loads the cache of the previous build from the given file, like [`BuildCache::load`]
+    fn load_file(path: &Path, force: bool) -> Self {
+        let previous = if force {
+            CacheData::default()
+        } else {
+            File::open(path)
+                .ok()
+                .and_then(|file| serde_yaml::from_reader(file).ok())
+                .unwrap_or_default()
+        };
+
+        Self {
+            previous,
+            current: Default::default(),
+        }
+    }
+
+    /// Saves the hashes of this build into the cache file
+    ///
+    /// Of the given output root, only the articles that were processed or skipped in this build are saved.
+    /// The articles of the other output roots in the project directory, like the one of the live reload,
+    /// are kept as long as their output files exist.
+    /// The articles outside of the project directory are dropped,
+    /// as they are left over from the builds into the other directories.
+    pub fn save(&self, output_root: &Path) -> Result<()> {
+        self.save_in_dir(Path::new(""), output_root)
+    }
+
+    /// Saves the hashes of this build into the cache file of the given project directory, like [`BuildCache::save`]
+    ///
+    /// The relative paths of the articles are relative to the project directory.
+    fn save_in_dir(&self, project_dir: &Path, output_root: &Path) -> Result<()> {
+        let mut data = CacheData {
+            articles: self
+                .previous
+                .articles
+                .iter()
+                .filter(|(html_path, _)| {
+                    !html_path.starts_with(output_root)
+                        && is_in_project(html_path)
+                        && project_dir.join(html_path).is_file()
+                })
+                .map(|(html_path, hash)| (html_path.clone(), hash.clone()))
+                .collect(),
+        };
+        data.articles.extend(self.current.articles.clone());
+
+        let path = &project_dir.join(CACHE_FILENAME);
+        let file =
+            File::create(path).map_err(DwwbError::io("creating the build cache file", path))?;
+        serde_yaml::to_writer(file, &data).map_err(|source| DwwbError::Yaml {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Removes the cache file from the current working directory, if it exists
+    pub fn remove() -> std::io::Result<()> {
+        match fs::remove_file(CACHE_FILENAME) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks if the given output file exists and was generated from inputs with the given hash in the previous build
+    pub fn is_fresh(&self, html_path: &Path, hash: &str) -> bool {
+        html_path.is_file()
+            && self
+                .previous
+                .articles
+                .get(html_path)
+                .map_or(false, |prev| prev == hash)
+    }
+
+    /// Records the hash of the inputs of the given output file for this build
+    pub fn insert(&mut self, html_path: PathBuf, hash: String) {
+        self.current.articles.insert(html_path, hash);
+    }
+}
+
+/// Checks if the path is relative to the project directory and doesn't go outside of it
+fn is_in_project(path: &Path) -> bool {
+    path.components()
+        .all(|comp| matches!(comp, Component::Normal(_) | Component::CurDir))
+}
+
+/// Returns the hash of the inputs that are shared by all of the articles
+///
+/// Includes the configuration, the stylesheet, the article template and all the possible partials next to it,
+/// and the shared pandoc defaults, like the titles and the script files.
+pub fn hash_shared_inputs(cfg: &DwwbConfig, defaults_data: &Mapping) -> Result<String> {
+    let mut hasher = InputHasher::new();
+    hasher
+        .update(env!("CARGO_PKG_VERSION"))
+        .update_yaml(cfg)?
+        .update_yaml(defaults_data)?
+        .update_file(cfg.inputs.style())?;
+
+    // the partials are looked up from the same directory with the same extension as the template
+    let template = cfg.inputs.article_template();
+    let template_dir = match template.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut templates = vec![template.to_path_buf()];
+    let read_dir_error = DwwbError::io("reading the template directory", template_dir);
+    let entries = fs::read_dir(template_dir)
+        .and_then(|entries| entries.collect::<std::io::Result<Vec<_>>>())
+        .map_err(read_dir_error)?;
+    for entry in entries {
+        let path = entry.path();
+        if path.is_file() && path.extension() == template.extension() {
+            templates.push(path);
+        }
+    }
+    templates[1..].sort();
+
+    for template in templates {
+        hasher
+            .update(template.to_string_lossy().as_bytes())
+            .update_file(&template)?;
+    }
+    Ok(hasher.finish())
+}
+
+/// Hashes the inputs of the articles
+#[derive(Debug, Clone, Default)]
+pub struct InputHasher(Sha256);
+
+impl InputHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the given bytes to the hash
+    pub fn update<B: AsRef<[u8]>>(&mut self, bytes: B) -> &mut Self {
+        let bytes = bytes.as_ref();
+        // prefix with the length so that consecutive inputs can't be confused with each other
+        self.0.update((bytes.len() as u64).to_le_bytes());
+        self.0.update(bytes);
+        self
+    }
+
+    /// Adds the YAML serialization of the given value to the hash
+    ///
+    /// Unlike the `Debug` output, the serialization is a stable format.
+    pub fn update_yaml<T: Serialize>(&mut self, value: &T) -> Result<&mut Self> {
+        let yaml = serde_yaml::to_string(value).map_err(|source| DwwbError::Yaml {
+            path: CACHE_FILENAME.into(),
+            source,
+        })?;
+        Ok(self.update(yaml))
+    }
+
+    /// Adds the contents of the given file to the hash
+    pub fn update_file(&mut self, path: &Path) -> Result<&mut Self> {
+        let contents =
+            fs::read(path).map_err(DwwbError::io("reading the file for the build cache", path))?;
+        Ok(self.update(contents))
+    }
+
+    /// Returns the hash as a hexadecimal string
+    pub fn finish(&self) -> String {
+        self.0
+            .clone()
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Returns a project with a stylesheet and an article template in a temporary directory
+    fn project() -> (TempDir, DwwbConfig) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::create_dir(dir.path().join("templates")).unwrap();
+        fs::write(path("style.css"), "body {}").unwrap();
+        fs::write(path("templates/article.html"), "$body$").unwrap();
+
+        let mut cfg = serde_yaml::to_value(DwwbConfig::default()).unwrap();
+        cfg["inputs"]["style"] = path("style.css").into();
+        cfg["inputs"]["article-template"] = path("templates/article.html").into();
+        (dir, serde_yaml::from_value(cfg).unwrap())
+    }
+
+    fn shared_hash(cfg: &DwwbConfig) -> String {
+        hash_shared_inputs(cfg, &Mapping::new()).unwrap()
+    }
+
+    #[test]
+    fn changed_shared_inputs_change_the_hash() {
+        let (dir, mut cfg) = project();
+        let hash = shared_hash(&cfg);
+        assert_eq!(shared_hash(&cfg), hash);
+
+        fs::write(dir.path().join("style.css"), "body { color: red; }").unwrap();
+        let style_hash = shared_hash(&cfg);
+        assert_ne!(style_hash, hash);
+
+        fs::write(
+            dir.path().join("templates/article.html"),
+            "<main>$body$</main>",
+        )
+        .unwrap();
+        let template_hash = shared_hash(&cfg);
+        assert_ne!(template_hash, style_hash);
+
+        // the partials are in the same directory as the template
+        fs::write(dir.path().join("templates/sidebar.html"), "").unwrap();
+        let partial_hash = shared_hash(&cfg);
+        assert_ne!(partial_hash, template_hash);
+
+        cfg.toc_depth += 1;
+        let config_hash = shared_hash(&cfg);
+        assert_ne!(config_hash, partial_hash);
+
+        let defaults = Mapping::from_iter([("variables".into(), Mapping::new().into())]);
+        assert_ne!(hash_shared_inputs(&cfg, &defaults).unwrap(), config_hash);
+    }
+
+    #[test]
+    fn only_unchanged_existing_outputs_are_fresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join(CACHE_FILENAME);
+        let html_path = dir.path().join("html/alice.html");
+        let missing_path = dir.path().join("html/bob.html");
+        fs::create_dir(dir.path().join("html")).unwrap();
+        fs::write(&html_path, "").unwrap();
+
+        let mut cache = BuildCache::default();
+        cache.insert(html_path.clone(), "hash".to_string());
+        cache.insert(missing_path.clone(), "hash".to_string());
+        cache
+            .save_in_dir(dir.path(), &dir.path().join("html"))
+            .unwrap();
+
+        let cache = BuildCache::load_file(&cache_path, false);
+        assert!(cache.is_fresh(&html_path, "hash"));
+        assert!(!cache.is_fresh(&html_path, "changed hash"));
+        assert!(!cache.is_fresh(&missing_path, "hash"));
+    }
+
+    #[test]
+    fn force_ignores_the_previous_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join(CACHE_FILENAME);
+        let html_path = dir.path().join("alice.html");
+        fs::write(&html_path, "").unwrap();
+
+        let mut cache = BuildCache::default();
+        cache.insert(html_path.clone(), "hash".to_string());
+        cache.save_in_dir(dir.path(), dir.path()).unwrap();
+
+        assert!(BuildCache::load_file(&cache_path, false).is_fresh(&html_path, "hash"));
+        assert!(!BuildCache::load_file(&cache_path, true).is_fresh(&html_path, "hash"));
+    }
+
+    #[test]
+    fn saving_keeps_only_the_existing_outputs_of_the_other_roots_in_the_project() {
+        let project_dir = tempfile::tempdir().unwrap();
+        let project_dir = project_dir.path();
+        let cache_path = project_dir.join(CACHE_FILENAME);
+        // the paths in the project are relative to it, like the ones of the builds in the working directory
+        let root = Path::new("html");
+        let other_root = Path::new("live");
+        let outside_dir = tempfile::tempdir().unwrap();
+        for path in [root, other_root] {
+            fs::create_dir(project_dir.join(path)).unwrap();
+        }
+
+        let kept = other_root.join("kept.html");
+        let deleted = other_root.join("deleted.html");
+        let outside = outside_dir.path().join("outside.html");
+        let parent = Path::new("..").join(&kept);
+        let old = root.join("old.html");
+        let new = root.join("new.html");
+        for path in [&kept, &outside, &old, &new] {
+            fs::write(project_dir.join(path), "").unwrap();
+        }
+
+        let mut previous = BuildCache::default();
+        for path in [&kept, &deleted, &outside, &parent, &old] {
+            previous.insert(path.clone(), "hash".to_string());
+        }
+        previous.save_in_dir(project_dir, other_root).unwrap();
+
+        let mut cache = BuildCache::load_file(&cache_path, false);
+        cache.insert(new.clone(), "hash".to_string());
+        cache.save_in_dir(project_dir, root).unwrap();
+
+        let cache = BuildCache::load_file(&cache_path, false);
+        let saved: Vec<_> = cache.previous.articles.keys().collect();
+        assert_eq!(saved, [&new, &kept]);
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+use serde_yaml::Mapping;
+
+use super::sidebar::{metadata_block, ArticleSidebarData};
+use super::template::Template;
+use crate::config::DwwbConfig;
+use crate::error::{DwwbError, Result};
+use crate::util::escape_markdown;
+
+/// A sub-article of the category in the category template
+///
+/// The template produces markdown and has no way to escape the values, so the texts are escaped beforehand.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SubArticle {
+    title: String,
+    link_url: String,
+    keywords: Vec<String>,
+}
+
+impl From<&ArticleSidebarData> for SubArticle {
+    fn from(article: &ArticleSidebarData) -> Self {
+        Self {
+            title: escape_markdown(&article.title),
+            link_url: article.link_url.clone(),
+            keywords: article
+                .keywords
+                .iter()
+                .map(|keyword| escape_markdown(keyword))
+                .collect(),
+        }
+    }
+}
+
+/// Generates the listing pages of the categories that have no article of their own
+///
+/// The category template produces the markdown source of each page,
+/// which is then converted like any other article.
+pub fn generate_category_pages(
+    cfg: &DwwbConfig,
+    root: &mut ArticleSidebarData,
+    template: &Template,
+) -> Result<()> {
+    // the index is never a category
+    for sub in &mut root.sub_articles {
+        generate_recursive(cfg, sub, template)?;
+    }
+    Ok(())
+}
+
+fn generate_recursive(
+    cfg: &DwwbConfig,
+    node: &mut ArticleSidebarData,
+    template: &Template,
+) -> Result<()> {
+    // the sub-categories first, so that they have urls when they are listed
+    for sub in &mut node.sub_articles {
+        generate_recursive(cfg, sub, template)?;
+    }
+
+    if node.md_file_path.is_some() || node.html_file_path.is_some() {
+        return Ok(());
+    }
+
+    let title = escape_markdown(&node.title);
+    let sub_articles: Vec<_> = node.sub_articles.iter().map(SubArticle::from).collect();
+    let variables = Mapping::from_iter([
+        ("id".into(), node.id.clone().into()),
+        ("title".into(), title.clone().into()),
+        (
+            "sub-articles".into(),
+            serde_yaml::to_value(sub_articles).unwrap(),
+        ),
+    ]);
+    let body = template
+        .render(&variables)
+        .map_err(|message| DwwbError::Template {
+            path: cfg.inputs.category_template().to_path_buf(),
+            message,
+        })?;
+
+    let source = metadata_block(&title) + &body;
+
+    node.set_generated(cfg, source);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn listings_are_escaped() {
+        let template = Template::load_or_default(
+            Path::new("missing/category.md"),
+            include_str!("../include/templates/category.md"),
+        )
+        .unwrap();
+        let mut root = ArticleSidebarData {
+            sub_articles: vec![ArticleSidebarData {
+                id: "c++".to_string(),
+                title: "C++".to_string(),
+                sub_articles: vec![ArticleSidebarData {
+                    id: "c++/draft".to_string(),
+                    title: "C++ *draft* [v2]".to_string(),
+                    md_file_path: Some("articles/c++/draft.md".into()),
+                    link_url: "articles/c%2B%2B/draft.html".to_string(),
+                    keywords: vec!["_wip_".to_string()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        generate_category_pages(&DwwbConfig::default(), &mut root, &template).unwrap();
+
+        let source = root.sub_articles[0].generated_source.as_deref().unwrap();
+        assert!(source.starts_with(&metadata_block("C\\+\\+")));
+        assert!(source.contains(
+            "\n* [C\\+\\+ \\*draft\\* \\[v2\\]](%ROOT%articles/c%2B%2B/draft.html) --- *\\_wip\\_*\n"
+        ));
+    }
+}
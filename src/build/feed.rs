@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+
+use super::search::article_text;
+use super::sidebar::{ArticleSidebarData, METADATA_BLOCK_REGEX};
+use super::sitemap::site_url_with_slash;
+use super::wikilink::WikiLinks;
+use crate::config::{DwwbConfig, FeedConfig};
+use crate::error::{DwwbError, Result};
+use crate::util::escape_html;
+
+/// The file name of the Atom feed, which is placed in the output root
+pub const ATOM_FILENAME: &str = "atom.xml";
+
+/// The file name of the RSS feed, which is placed in the output root
+pub const RSS_FILENAME: &str = "rss.xml";
+
+/// The maximum length of the summaries made from the text of the articles, in characters
+const SUMMARY_LENGTH: usize = 300;
+
+/// A recently changed article in the feed
+struct FeedEntry<'a> {
+    article: &'a ArticleSidebarData,
+    /// The absolute url of the article
+    url: String,
+    updated: DateTime<FixedOffset>,
+    summary: String,
+}
+
+/// Writes the feeds of the most recently changed articles into the output root, if they are enabled in the configuration
+///
+/// The articles are sorted by their `updated` or `date` metadata values, or the dates of their latest commits
+/// or the modification times of their files.
+/// The generated articles aren't included.
+///
+/// Returns the paths of the written files.
+pub fn write_feeds(
+    cfg: &DwwbConfig,
+    root: &ArticleSidebarData,
+    links: &WikiLinks,
+) -> Result<Vec<PathBuf>> {
+    let (Some(feed), Some(site_url)) = (&cfg.feed, &cfg.site_url) else {
+        return Ok(Vec::new());
+    };
+    let site_url = site_url_with_slash(site_url);
+
+    let mut articles: Vec<_> = root
+        .iter()
+        .filter(|article| article.md_file_path.is_some())
+        .filter_map(|article| Some((article, article.last_modified()?)))
+        .collect();
+    articles.sort_by(|(a, a_updated), (b, b_updated)| {
+        b_updated.cmp(a_updated).then_with(|| a.id.cmp(&b.id))
+    });
+    let entries: Vec<_> = articles
+        .into_iter()
+        .take(feed.entries)
+        .map(|(article, updated)| FeedEntry {
+            article,
+            url: format!("{site_url}{}", article.link_url),
+            updated,
+            summary: summary(links, article),
+        })
+        .collect();
+
+    let mut written = Vec::new();
+    let path = cfg.outputs.root().join(ATOM_FILENAME);
+    fs::write(&path, atom_feed(cfg, feed, &site_url, &entries))
+        .map_err(DwwbError::io("writing the feed", &path))?;
+    written.push(path);
+
+    if feed.rss {
+        let path = cfg.outputs.root().join(RSS_FILENAME);
+        fs::write(&path, rss_feed(cfg, feed, &site_url, &entries))
+            .map_err(DwwbError::io("writing the feed", &path))?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+fn atom_feed(cfg: &DwwbConfig, feed: &FeedConfig, site_url: &str, entries: &[FeedEntry]) -> String {
+    let date = |date: DateTime<FixedOffset>| date.to_rfc3339_opts(SecondsFormat::Secs, true);
+    // an empty feed was last updated when it was written
+    let updated = entries
+        .first()
+        .map(|entry| entry.updated)
+        .unwrap_or_else(|| DateTime::<Utc>::from(SystemTime::now()).fixed_offset());
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n  \
+         <title>{title}</title>\n  \
+         <id>{site_url}</id>\n  \
+         <link href=\"{site_url}\" />\n  \
+         <link rel=\"self\" href=\"{site_url}{ATOM_FILENAME}\" />\n  \
+         <updated>{updated}</updated>\n  \
+         <author><name>{author}</name></author>\n",
+        title = escape_html(feed.title.as_ref().unwrap_or(&cfg.name)),
+        site_url = escape_html(site_url),
+        updated = date(updated),
+        author = escape_html(feed.author.as_ref().unwrap_or(&cfg.name)),
+    );
+    for entry in entries {
+        let url = escape_html(&entry.url);
+        xml.push_str(&format!(
+            "  <entry>\n    \
+             <title>{}</title>\n    \
+             <id>{url}</id>\n    \
+             <link href=\"{url}\" />\n",
+            escape_html(&entry.article.title)
+        ));
+        if let Some(published) = entry.article.date {
+            xml.push_str(&format!("    <published>{}</published>\n", date(published)));
+        }
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n    \
+             <summary>{}</summary>\n  \
+             </entry>\n",
+            date(entry.updated),
+            escape_html(&entry.summary)
+        ));
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss_feed(cfg: &DwwbConfig, feed: &FeedConfig, site_url: &str, entries: &[FeedEntry]) -> String {
+    let title = escape_html(feed.title.as_ref().unwrap_or(&cfg.name));
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n  \
+         <title>{title}</title>\n  \
+         <link>{site_url}</link>\n  \
+         <description>{title}</description>\n",
+        site_url = escape_html(site_url),
+    );
+    for entry in entries {
+        let url = escape_html(&entry.url);
+        xml.push_str(&format!(
+            "  <item>\n    \
+             <title>{}</title>\n    \
+             <link>{url}</link>\n    \
+             <guid>{url}</guid>\n    \
+             <pubDate>{}</pubDate>\n    \
+             <description>{}</description>\n  \
+             </item>\n",
+            escape_html(&entry.article.title),
+            entry.updated.to_rfc2822(),
+            escape_html(&entry.summary)
+        ));
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// Returns the summary of the article for the feeds
+///
+/// That is the `summary`, the `description` or the `abstract` metadata value,
+/// or the start of the text of the article without any of them.
+/// The wiki links are replaced by their labels in both.
+fn summary(links: &WikiLinks, article: &ArticleSidebarData) -> String {
+    let (Some(md_path), Some(source)) = (&article.md_file_path, &article.source) else {
+        return String::new();
+    };
+
+    let metadata: HashMap<String, serde_yaml::Value> = METADATA_BLOCK_REGEX
+        .captures(source)
+        .and_then(|caps| serde_yaml::from_str(caps.get(1)?.as_str()).ok())
+        .unwrap_or_default();
+    let summary = ["summary", "description", "abstract"]
+        .into_iter()
+        .find_map(|key| metadata.get(key)?.as_str());
+    if let Some(summary) = summary {
+        return article_text(links, md_path, summary);
+    }
+
+    let text = article_text(links, md_path, source);
+    match text.char_indices().nth(SUMMARY_LENGTH) {
+        Some((end, _)) => {
+            // cut at the last whole word
+            let end = text[..end].rfind(' ').unwrap_or(end);
+            format!("{}…", &text[..end])
+        }
+        None => text,
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+use chrono::{DateTime, FixedOffset};
+
+use super::sidebar::{metadata_block, ArticleLink, ArticleSidebarData};
+use crate::config::DwwbConfig;
+use crate::error::Result;
+use crate::util::escape_markdown;
+
+/// The id of the generated recent changes page, which is placed in the reserved `_special` directory of the articles
+pub const RECENT_CHANGES_ID: &str = "_special/recent-changes";
+
+/// The number of the latest commits listed on the recent changes page
+const RECENT_CHANGES_COUNT: usize = 50;
+
+/// A commit that changed some of the articles
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub date: DateTime<FixedOffset>,
+    pub author: String,
+    pub subject: String,
+    /// The changed article files, relative to the project directory
+    pub files: Vec<PathBuf>,
+}
+
+/// The git history of a single article
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHistory {
+    /// The date of the latest commit that changed the article
+    pub last_modified: DateTime<FixedOffset>,
+    /// The authors of the commits that changed the article, the most active first
+    pub contributors: Vec<String>,
+}
+
+/// Reads the commits that changed the index or the articles from the git repository of the project, the latest first
+///
+/// Only the local repository is read.
+/// Returns `None` if the project isn't in a git repository, it has no commits, or git isn't installed.
+pub fn read_history(cfg: &DwwbConfig) -> Option<Vec<Commit>> {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--relative",
+            "--no-renames",
+            "--name-only",
+            "--format=%x1e%aI%x1f%an%x1f%s",
+            "--",
+        ])
+        .arg(cfg.inputs.index())
+        .arg(cfg.inputs.articles_dir())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let log = String::from_utf8_lossy(&output.stdout);
+    let commits = log
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut fields = lines.next()?.splitn(3, '\x1f');
+            Some(Commit {
+                date: DateTime::parse_from_rfc3339(fields.next()?).ok()?,
+                author: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+                files: lines
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .collect(),
+            })
+        })
+        .collect();
+    Some(commits)
+}
+
+/// Sets the git histories of all the articles in the tree from the given commits
+pub fn set_file_histories(root: &mut ArticleSidebarData, commits: &[Commit]) {
+    root.for_each_mut(&mut |node| {
+        let Some(md_path) = &node.md_file_path else {
+            return;
+        };
+        let mut last_modified = None;
+        let mut counts = HashMap::<&str, usize>::new();
+        for commit in commits
+            .iter()
+            .filter(|commit| commit.files.contains(md_path))
+        {
+            // the commits are the latest first
+            last_modified.get_or_insert(commit.date);
+            *counts.entry(&commit.author).or_default() += 1;
+        }
+
+        node.history = last_modified.map(|last_modified| {
+            let mut contributors: Vec<_> = counts.into_iter().collect();
+            contributors.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+            FileHistory {
+                last_modified,
+                contributors: contributors
+                    .into_iter()
+                    .map(|(author, _)| author.to_string())
+                    .collect(),
+            }
+        });
+    });
+}
+
+/// Generates the recent changes page, which lists the latest commits and the articles they changed
+///
+/// Fails if there already is an article or a category with the same id.
+pub fn generate_recent_changes_page(
+    cfg: &DwwbConfig,
+    root: &mut ArticleSidebarData,
+    commits: &[Commit],
+) -> Result<()> {
+    let mut page = ArticleSidebarData {
+        id: RECENT_CHANGES_ID.to_string(),
+        title: cfg.recent_changes_title.clone(),
+        ..Default::default()
+    };
+    page.set_generated(cfg, String::new());
+    root.check_generated_id(cfg, &page)?;
+
+    // the links to the articles by their markdown files
+    let articles: HashMap<_, _> = root
+        .iter()
+        .filter_map(|article| Some((article.md_file_path.as_deref()?, ArticleLink::from(article))))
+        .collect();
+
+    let mut source = metadata_block(&cfg.recent_changes_title);
+    let mut day = String::new();
+    for commit in commits.iter().take(RECENT_CHANGES_COUNT) {
+        // the commits are grouped by their days
+        let date = commit.date.format("%Y-%m-%d").to_string();
+        if date != day {
+            let _ = write!(source, "\n## {date}\n\n");
+            day = date;
+        }
+
+        // the deleted articles have no page to link to
+        let changed: Vec<_> = commit
+            .files
+            .iter()
+            .map(|path| match articles.get(path.as_path()) {
+                Some(article) => format!(
+                    "[{}](%ROOT%{})",
+                    escape_markdown(&article.title),
+                    article.link_url
+                ),
+                None => format!("~~{}~~", escape_markdown(&path.display().to_string())),
+            })
+            .collect();
+        let _ = writeln!(
+            source,
+            "* {} --- {} *({})*",
+            changed.join(", "),
+            escape_markdown(&commit.subject),
+            escape_markdown(&commit.author)
+        );
+    }
+
+    page.generated_source = Some(source);
+    root.sub_articles.push(page);
+    Ok(())
+}
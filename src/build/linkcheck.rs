@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use globwalk::GlobWalkerBuilder;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::{DwwbError, Result};
+
+lazy_static! {
+    /// Matches the urls of the `href` and `src` attributes, with the url in the first capture group
+    static ref LINK_ATTRIBUTE_REGEX: Regex = Regex::new(r#"\s(?:href|src)="([^"]*)""#).unwrap();
+    /// Matches the `id` attributes, with the id in the first capture group
+    static ref ID_ATTRIBUTE_REGEX: Regex = Regex::new(r#"\sid="([^"]*)""#).unwrap();
+    /// Matches the urls that aren't relative, like the ones with a scheme or a host
+    static ref ABSOLUTE_URL_REGEX: Regex = Regex::new(r"^(?:[a-zA-Z][a-zA-Z0-9+.-]*:|/)").unwrap();
+}
+
+/// Checks that the relative links of all the html files in the output directory point to existing files
+///
+/// The fragments of the links must match an element id in the linked html file.
+/// The absolute urls aren't checked.
+/// The given virtual files, with the urls relative to the output root, are treated as existing files
+/// even though they aren't in the output directory.
+///
+/// Returns the broken links.
+pub fn check_links(output_root: &Path, virtual_files: &[String]) -> Result<Vec<DwwbError>> {
+    let walker = GlobWalkerBuilder::new(output_root, "**/*.html")
+        .file_type(globwalk::FileType::FILE)
+        .build()
+        .map_err(DwwbError::glob(output_root))?;
+
+    // the pages are read before checking, so that the ids of every page are known
+    let mut pages = HashMap::new();
+    for entry in walker {
+        let path = entry.map_err(DwwbError::glob(output_root))?.into_path();
+        let html = fs::read_to_string(&path).map_err(DwwbError::io("reading the file", &path))?;
+        pages.insert(path, html);
+    }
+    let ids: HashMap<&Path, HashSet<String>> = pages
+        .iter()
+        .map(|(path, html)| {
+            let ids = ID_ATTRIBUTE_REGEX
+                .captures_iter(html)
+                .map(|caps| unescape_html(&caps[1]))
+                .collect();
+            (path.as_path(), ids)
+        })
+        .collect();
+    let virtual_files: HashSet<PathBuf> = virtual_files
+        .iter()
+        .map(|url| resolve_path(output_root, url))
+        .collect();
+
+    let mut broken = Vec::new();
+    for (path, html) in &pages {
+        for caps in LINK_ATTRIBUTE_REGEX.captures_iter(html) {
+            let url = unescape_html(&caps[1]);
+            if let Err(reason) = check_link(output_root, path, &url, &ids, &virtual_files) {
+                broken.push(DwwbError::BrokenLink {
+                    path: path.clone(),
+                    url,
+                    reason,
+                });
+            }
+        }
+    }
+
+    broken.sort_by(|a, b| a.path().cmp(&b.path()));
+    Ok(broken)
+}
+
+/// Checks a single link of the html file at the given path
+///
+/// The error is the reason why the link is broken.
+fn check_link(
+    output_root: &Path,
+    path: &Path,
+    url: &str,
+    ids: &HashMap<&Path, HashSet<String>>,
+    virtual_files: &HashSet<PathBuf>,
+) -> Result<(), String> {
+    if ABSOLUTE_URL_REGEX.is_match(url) {
+        return Ok(());
+    }
+
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let url = url.split('?').next().unwrap_or_default();
+
+    let target = if url.is_empty() {
+        path.to_path_buf()
+    } else {
+        let target = resolve_path(path.parent().unwrap_or(output_root), url);
+        if !target.starts_with(output_root) {
+            return Err("it points outside of the output directory".to_string());
+        }
+        if !target.exists() && !virtual_files.contains(&target) {
+            return Err(format!("the file '{}' does not exist", target.display()));
+        }
+        target
+    };
+
+    match fragment.filter(|fragment| !fragment.is_empty()) {
+        Some(fragment) => {
+            let fragment = url_escape::decode(fragment);
+            match ids.get(target.as_path()) {
+                Some(ids) if ids.contains(fragment.as_ref()) => Ok(()),
+                Some(_) => Err(format!(
+                    "no element with the id '{fragment}' in the file '{}'",
+                    target.display()
+                )),
+                None => Err(format!(
+                    "the file '{}' is not a html page, so it can't have the fragment '{fragment}'",
+                    target.display()
+                )),
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+/// Joins the url to the directory, resolving the `.` and `..` segments without touching the file system
+fn resolve_path(dir: &Path, url: &str) -> PathBuf {
+    let mut path = dir.to_path_buf();
+    for component in Path::new(url_escape::decode(url).as_ref()).components() {
+        match component {
+            Component::ParentDir => {
+                // going above the start of a relative path keeps the `..`
+                if path.file_name().is_some() {
+                    path.pop();
+                } else {
+                    path.push("..");
+                }
+            }
+            Component::Normal(segment) => path.push(segment),
+            _ => {}
+        }
+    }
+    path
+}
+
+/// Replaces the html character references that can appear in the attribute values
+fn unescape_html(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_resolved() {
+        let dir = Path::new("html/articles");
+        assert_eq!(
+            resolve_path(dir, "alice.html"),
+            Path::new("html/articles/alice.html")
+        );
+        assert_eq!(
+            resolve_path(dir, "./people/../alice.html"),
+            Path::new("html/articles/alice.html")
+        );
+        assert_eq!(
+            resolve_path(dir, "../index.html"),
+            Path::new("html/index.html")
+        );
+        assert_eq!(resolve_path(dir, "../../../secret"), Path::new("../secret"));
+        assert_eq!(
+            resolve_path(dir, "caf%C3%A9%20au%20lait.html"),
+            Path::new("html/articles/café au lait.html")
+        );
+        assert_eq!(
+            resolve_path(dir, "%2E%2E/index.html"),
+            Path::new("html/index.html")
+        );
+    }
+
+    #[test]
+    fn links_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let page = root.join("index.html");
+        let style = root.join("style.css");
+        fs::write(&page, "").unwrap();
+        fs::write(&style, "").unwrap();
+        let ids = HashMap::from([(
+            page.as_path(),
+            HashSet::from(["intro".to_string(), "ümlaut".to_string()]),
+        )]);
+        let virtual_files = HashSet::from([root.join("scripts/live.js")]);
+        let check = |url| check_link(root, &page, url, &ids, &virtual_files);
+
+        assert_eq!(check("https://example.com/missing.html"), Ok(()));
+        assert_eq!(check("style.css?v=1"), Ok(()));
+        assert_eq!(check("scripts/live.js"), Ok(()));
+        assert_eq!(check("#intro"), Ok(()));
+        assert_eq!(check("index.html#%C3%BCmlaut"), Ok(()));
+        assert_eq!(check("index.html#"), Ok(()));
+        assert!(check("missing.html")
+            .unwrap_err()
+            .contains("does not exist"));
+        assert!(check("../index.html")
+            .unwrap_err()
+            .contains("outside of the output directory"));
+        assert!(check("#outro")
+            .unwrap_err()
+            .contains("no element with the id 'outro'"));
+        assert!(check("style.css#intro")
+            .unwrap_err()
+            .contains("not a html page"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use serde_yaml::{Mapping, Value};
+
+use super::filter::variable_replacer_filter;
+use super::sidebar::METADATA_BLOCK_REGEX;
+use super::template::Template;
+use crate::config::DwwbConfig;
+use crate::error::{DwwbError, MetadataError, Result};
+use crate::util::{escape_html, path_to_url};
+
+/// Renders the articles in-process with a markdown parser and a native implementation of the pandoc templates
+///
+/// Used instead of pandoc when the `renderer` of the configuration is `native`.
+#[derive(Debug)]
+pub struct NativeRenderer {
+    template: Template,
+    /// The template variables shared by all of the articles
+    variables: Mapping,
+    toc_depth: u32,
+}
+
+impl NativeRenderer {
+    /// Loads the article template and its partials
+    ///
+    /// The given variables are available in the template of every article.
+    pub fn new(cfg: &DwwbConfig, mut variables: Mapping) -> Result<Self> {
+        variables.insert(
+            "css".into(),
+            vec![Value::from(path_to_url(cfg.outputs.style()))].into(),
+        );
+        variables.insert("toc".into(), true.into());
+
+        Ok(Self {
+            template: Template::load(cfg.inputs.article_template())?,
+            variables,
+            toc_depth: cfg.toc_depth,
+        })
+    }
+
+    /// Renders the markdown source of the given article into the html file
+    ///
+    /// The article specific variables override the shared variables and the metadata of the article.
+    pub fn render_article(
+        &self,
+        md_path: &Path,
+        source: String,
+        html_path: &Path,
+        root_url: &str,
+        article_variables: &Mapping,
+    ) -> Result<()> {
+        // same as the pandoc filter, which also replaces the occurrences in the metadata
+        let source = variable_replacer_filter(root_url.to_string())(source);
+
+        let metadata_error = |source| DwwbError::Metadata {
+            path: md_path.to_path_buf(),
+            source,
+        };
+        let (metadata, body_source) =
+            split_metadata(&source).ok_or_else(|| metadata_error(MetadataError::MissingBlock))?;
+        let metadata: Mapping =
+            serde_yaml::from_str(metadata).map_err(|e| metadata_error(e.into()))?;
+
+        let mut variables = Mapping::new();
+
+        // the plain text versions of the metadata, used in the html head
+        for (key, meta_key) in [
+            ("pagetitle", "title"),
+            ("author-meta", "author"),
+            ("date-meta", "date"),
+            ("description-meta", "description"),
+        ] {
+            if let Some(value) = metadata.get(meta_key) {
+                variables.insert(key.into(), plain_text_value(value));
+            }
+        }
+        // pandoc also treats the metadata strings as markdown
+        for (key, value) in metadata {
+            variables.insert(key, render_metadata_value(value));
+        }
+        variables.extend(self.variables.clone());
+        variables.extend(article_variables.clone());
+        variables.insert("base-url".into(), root_url.into());
+
+        let (body, headings) = render_markdown(&body_source);
+        variables.insert("body".into(), body.into());
+        variables.insert(
+            "table-of-contents".into(),
+            table_of_contents(&headings, self.toc_depth as usize).into(),
+        );
+
+        let output = self
+            .template
+            .render(&variables)
+            .map_err(|message| DwwbError::Template {
+                path: md_path.to_path_buf(),
+                message,
+            })?;
+
+        fs::write(html_path, output).map_err(DwwbError::io("writing the file", html_path))
+    }
+}
+
+/// A heading of the article, for the table of contents
+struct Heading {
+    level: usize,
+    id: String,
+    /// The contents of the heading as html
+    html: String,
+}
+
+/// Splits the markdown source into the YAML metadata and the rest of the article
+fn split_metadata(source: &str) -> Option<(&str, String)> {
+    let captures = METADATA_BLOCK_REGEX.captures(source)?;
+    let block = captures.get(0)?;
+    let metadata = captures.get(1)?.as_str();
+
+    let body = format!("{}\n\n{}", &source[..block.start()], &source[block.end()..]);
+    Some((metadata, body))
+}
+
+/// Renders the markdown into html and collects its headings
+///
+/// The headings without an explicit identifier get one generated in the same way as pandoc does.
+fn render_markdown(source: &str) -> (String, Vec<Heading>) {
+    let mut events: Vec<Event> = Parser::new_ext(source, markdown_options()).collect();
+
+    let mut headings = Vec::new();
+    let mut used_ids = HashSet::new();
+    // the explicit identifiers are reserved first, so that the generated ones don't collide with them
+    for event in &events {
+        if let Event::Start(Tag::Heading(_, Some(id), _)) = event {
+            used_ids.insert(id.to_string());
+        }
+    }
+
+    let mut i = 0;
+    while i < events.len() {
+        if let Event::Start(Tag::Heading(level, id, classes)) = &events[i] {
+            let end = i + events[i..]
+                .iter()
+                .position(|event| matches!(event, Event::End(Tag::Heading(..))))
+                .unwrap_or(events.len() - i);
+            let contents = &events[i + 1..end];
+
+            let id = match id {
+                Some(id) => id.to_string(),
+                None => unique_identifier(&plain_text(contents), &mut used_ids),
+            };
+            let mut contents_html = String::new();
+            html::push_html(&mut contents_html, contents.iter().cloned());
+
+            let class = if classes.is_empty() {
+                String::new()
+            } else {
+                format!(" class=\"{}\"", escape_html(classes.join(" ")))
+            };
+            let level = *level;
+
+            events[i] = Event::Html(format!("<{level} id=\"{}\"{class}>", escape_html(&id)).into());
+            if end < events.len() {
+                events[end] = Event::Html(format!("</{level}>\n").into());
+            }
+
+            headings.push(Heading {
+                level: level as usize,
+                id,
+                html: contents_html,
+            });
+            i = end;
+        }
+        i += 1;
+    }
+
+    let mut body = String::new();
+    html::push_html(&mut body, events.into_iter());
+    (body, headings)
+}
+
+pub(super) fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_SMART_PUNCTUATION
+        | Options::ENABLE_HEADING_ATTRIBUTES
+}
+
+/// Returns the text content of the events without any formatting
+fn plain_text(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+            Event::SoftBreak | Event::HardBreak => Some(" "),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generates a heading identifier with the same rules as pandoc's `auto_identifiers` extension
+///
+/// <https://pandoc.org/MANUAL.html#extension-auto_identifiers>
+pub(super) fn heading_identifier(text: &str) -> String {
+    let id: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace() || matches!(c, '_' | '-' | '.'))
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .flat_map(char::to_lowercase)
+        .skip_while(|c| !c.is_alphabetic())
+        .collect();
+    if id.is_empty() {
+        "section".to_string()
+    } else {
+        id
+    }
+}
+
+/// Generates a heading identifier like [`heading_identifier`] that isn't in the used identifiers yet
+///
+/// The duplicates get a numeric suffix, like `intro-1`.
+fn unique_identifier(text: &str, used_ids: &mut HashSet<String>) -> String {
+    let id = heading_identifier(text);
+    let mut unique = id.clone();
+    let mut n = 1;
+    while used_ids.contains(&unique) {
+        unique = format!("{id}-{n}");
+        n += 1;
+    }
+    used_ids.insert(unique.clone());
+    unique
+}
+
+/// Generates the nested list of links to the headings up to the given level
+fn table_of_contents(headings: &[Heading], depth: usize) -> String {
+    let mut html = String::new();
+    // the heading levels of the currently open lists
+    let mut levels: Vec<usize> = Vec::new();
+
+    for heading in headings.iter().filter(|h| h.level <= depth) {
+        match levels.last().copied() {
+            None => {
+                html.push_str("<ul>\n");
+                levels.push(heading.level);
+            }
+            Some(top) if heading.level > top => {
+                html.push_str("\n<ul>\n");
+                levels.push(heading.level);
+            }
+            Some(_) => {
+                html.push_str("</li>\n");
+                while levels.len() > 1 && heading.level <= levels[levels.len() - 2] {
+                    levels.pop();
+                    html.push_str("</ul>\n</li>\n");
+                }
+                *levels.last_mut().unwrap() = heading.level;
+            }
+        }
+        html.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            escape_html(&heading.id),
+            heading.html
+        ));
+    }
+
+    if !levels.is_empty() {
+        html.push_str("</li>\n");
+        for _ in 1..levels.len() {
+            html.push_str("</ul>\n</li>\n");
+        }
+        html.push_str("</ul>");
+    }
+    html
+}
+
+/// Renders the strings in the metadata value as inline markdown
+fn render_metadata_value(value: Value) -> Value {
+    match value {
+        Value::String(s) => render_inline_markdown(&s).into(),
+        Value::Sequence(seq) => seq.into_iter().map(render_metadata_value).collect(),
+        Value::Mapping(map) => Value::Mapping(
+            map.into_iter()
+                .map(|(k, v)| (k, render_metadata_value(v)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn render_inline_markdown(source: &str) -> String {
+    let mut output = String::new();
+    html::push_html(&mut output, Parser::new_ext(source, markdown_options()));
+
+    // remove the paragraph around single line values
+    let trimmed = output.trim_end();
+    match trimmed
+        .strip_prefix("<p>")
+        .and_then(|s| s.strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => output,
+    }
+}
+
+/// Converts the metadata value into escaped plain text, for the html head
+fn plain_text_value(value: &Value) -> Value {
+    match value {
+        Value::String(s) => {
+            let events: Vec<Event> = Parser::new_ext(s, markdown_options()).collect();
+            escape_html(plain_text(&events)).into()
+        }
+        Value::Sequence(seq) => seq.iter().map(plain_text_value).collect(),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_follow_pandoc() {
+        let mut used_ids = HashSet::new();
+        assert_eq!(
+            unique_identifier("Heading identifiers in HTML", &mut used_ids),
+            "heading-identifiers-in-html"
+        );
+        assert_eq!(
+            unique_identifier("Dogs?--in *my* house?", &mut used_ids),
+            "dogs--in-my-house"
+        );
+        assert_eq!(
+            unique_identifier("3. Applications", &mut used_ids),
+            "applications"
+        );
+        assert_eq!(unique_identifier("33", &mut used_ids), "section");
+    }
+
+    #[test]
+    fn identifiers_are_deduplicated() {
+        let mut used_ids = HashSet::new();
+        assert_eq!(unique_identifier("Intro", &mut used_ids), "intro");
+        assert_eq!(unique_identifier("Intro", &mut used_ids), "intro-1");
+        assert_eq!(unique_identifier("Intro", &mut used_ids), "intro-2");
+        assert_eq!(unique_identifier("", &mut used_ids), "section");
+        assert_eq!(unique_identifier("", &mut used_ids), "section-1");
+    }
+
+    #[test]
+    fn explicit_identifiers_are_reserved() {
+        let (html, headings) = render_markdown("# Intro\n\n# Other {#intro}\n");
+        let ids: Vec<_> = headings.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, ["intro-1", "intro"]);
+        assert!(html.contains("<h1 id=\"intro-1\">Intro</h1>"));
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::Serialize;
+
+use super::native::markdown_options;
+use super::sidebar::{metadata_block, ArticleSidebarData, METADATA_BLOCK_REGEX};
+use super::wikilink::WikiLinks;
+use crate::config::DwwbConfig;
+use crate::error::{DwwbError, Result};
+use crate::util::path_to_url;
+
+/// The id of the generated search page, which is placed in the reserved `_special` directory of the articles
+pub const SEARCH_ID: &str = "_special/search";
+
+/// The file name of the JSON search index, which is placed in the output root
+pub const SEARCH_INDEX_FILENAME: &str = "search-index.json";
+
+/// The file name of the search index as a script, which is placed in the output root
+///
+/// The browsers don't allow fetching the JSON file from the local file system,
+/// so the search script loads the index as a script that assigns it to `window.dwwbSearchIndex`.
+pub const SEARCH_SCRIPT_FILENAME: &str = "search-index.js";
+
+/// The file name of the search script, which is placed in the scripts directory
+pub const SEARCH_JS_FILENAME: &str = "search.js";
+
+/// A single article in the search index
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SearchEntry<'a> {
+    title: &'a str,
+    keywords: &'a [String],
+    link_url: &'a str,
+    /// The plain text of the article, without the markup and the metadata block
+    body: String,
+}
+
+/// Generates the search page, which shows the results of the search script
+///
+/// Fails if there already is an article or a category with the same id.
+pub fn generate_search_page(cfg: &DwwbConfig, root: &mut ArticleSidebarData) -> Result<()> {
+    let mut page = ArticleSidebarData {
+        id: SEARCH_ID.to_string(),
+        title: cfg.search_title.clone(),
+        ..Default::default()
+    };
+    let source = metadata_block(&cfg.search_title)
+        + "<div id=\"search-results\">\n<noscript>The search requires JavaScript.</noscript>\n</div>\n";
+    page.set_generated(cfg, source);
+    root.check_generated_id(cfg, &page)?;
+    root.sub_articles.push(page);
+    Ok(())
+}
+
+/// Writes the built-in search script into the scripts output directory if the project has no search script of its own
+///
+/// The projects created before the search was added have no search script, and the search page does nothing without one.
+///
+/// Returns the url of the written script relative to the output root and the path of the written file.
+pub fn write_default_search_script(
+    cfg: &DwwbConfig,
+    script_files: &[String],
+) -> Result<Option<(String, PathBuf)>> {
+    let url = path_to_url(cfg.outputs.scripts_dir().join(SEARCH_JS_FILENAME));
+    if script_files.contains(&url) {
+        return Ok(None);
+    }
+
+    let path = cfg.outputs.root().join(&url);
+    fs::write(&path, include_str!("../include/search.js"))
+        .map_err(DwwbError::io("writing the search script", &path))?;
+    Ok(Some((url, path)))
+}
+
+/// Writes the search index of all the articles in the tree into the output root
+///
+/// The index is written both as JSON and as a script for the search script.
+///
+/// Returns the paths of the written files.
+pub fn write_search_index(
+    cfg: &DwwbConfig,
+    root: &ArticleSidebarData,
+    links: &WikiLinks,
+) -> Result<Vec<PathBuf>> {
+    let entries: Vec<_> = root
+        .input_articles()
+        .map(|(node, md_path, source)| SearchEntry {
+            title: &node.title,
+            keywords: &node.keywords,
+            link_url: &node.link_url,
+            body: article_text(links, md_path, source),
+        })
+        .collect();
+
+    // serializing strings can't fail
+    let json = serde_json::to_string(&entries).unwrap_or_default();
+    let json_path = cfg.outputs.root().join(SEARCH_INDEX_FILENAME);
+    fs::write(&json_path, &json).map_err(DwwbError::io("writing the search index", &json_path))?;
+
+    let script_path = cfg.outputs.root().join(SEARCH_SCRIPT_FILENAME);
+    fs::write(&script_path, format!("window.dwwbSearchIndex = {json};\n"))
+        .map_err(DwwbError::io("writing the search index", &script_path))?;
+
+    Ok(vec![json_path, script_path])
+}
+
+/// Converts the markdown source of the article into plain text like [`plain_text`], with the wiki links resolved
+///
+/// The wiki links are replaced so that only their labels end up in the text.
+/// The invalid links are left as they are, as they are reported when the article is built.
+pub fn article_text(links: &WikiLinks, md_path: &Path, source: &str) -> String {
+    match links.replace_links(md_path, source, "") {
+        Ok(source) => plain_text(&source),
+        Err(_) => plain_text(source),
+    }
+}
+
+/// Converts the markdown source into plain text with the whitespace collapsed
+pub fn plain_text(source: &str) -> String {
+    let source = METADATA_BLOCK_REGEX.replace(source, "");
+
+    let mut text = String::new();
+    for event in Parser::new_ext(&source, markdown_options()) {
+        match event {
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::SoftBreak | Event::HardBreak | Event::End(Tag::Paragraph | Tag::Heading(..)) => {
+                text.push(' ')
+            }
+            Event::End(Tag::Item | Tag::TableCell | Tag::CodeBlock(_)) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
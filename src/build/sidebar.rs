@@ -4,39 +4,95 @@ use std::io::Read;
 use std::ops::{Index, IndexMut};
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use lazy_static::lazy_static;
 use regex::Regex;
-use serde::Serialize;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 
-use crate::config::DwwbConfig;
+use super::history::FileHistory;
+use crate::config::{DwwbConfig, SidebarSort};
+use crate::error::{DwwbError, MetadataError, Result};
 use crate::util::path_to_url;
-use crate::uw;
+
+lazy_static! {
+    /// Matches the YAML metadata block of an article, with the block's contents in the first capture group
+    pub static ref METADATA_BLOCK_REGEX: Regex =
+        Regex::new(r"(?msx)(?:\A|\r?\n\r?\n)(---\s*?$.*?)^(?:---|\.\.\.)\s*?$").unwrap();
+}
+
+/// The directory of the articles that is reserved for the generated special pages, like the search page
+pub const SPECIAL_PAGES_DIR: &str = "_special";
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ArticleSidebarData {
+    /// The path of the article in the articles directory without the extension, like `people/alice`
+    ///
+    /// The index article's id is its file name without the extension.
     pub id: String,
     pub title: String,
     pub md_file_path: Option<PathBuf>,
     pub html_file_path: Option<PathBuf>,
     pub link_url: String,
     pub keywords: Vec<String>,
+    /// The `weight` or `order` metadata value, used for sorting the sidebar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<i64>,
+    /// The `date` metadata value, if it's a date like `2024-01-31` or an RFC 3339 date and time
+    #[serde(skip)]
+    pub date: Option<DateTime<FixedOffset>>,
+    /// The `updated` metadata value, in the same formats as the `date`
+    #[serde(skip)]
+    pub updated: Option<DateTime<FixedOffset>>,
     pub sub_articles: Vec<Self>,
+    /// The markdown source of a generated article, which has no input file
+    #[serde(skip)]
+    pub generated_source: Option<String>,
+    /// The markdown source of the input file, which is read along with the metadata
+    ///
+    /// Kept so that the file is read only once per build.
+    #[serde(skip)]
+    pub(crate) source: Option<String>,
+    /// The links to the tag pages of the keywords
+    #[serde(skip)]
+    pub(crate) keyword_links: Vec<ArticleLink>,
+    /// The git history of the markdown file, if the project is in a git repository
+    #[serde(skip)]
+    pub(crate) history: Option<FileHistory>,
+}
+
+/// The title and the url of an article, for the lists of links in the templates
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArticleLink {
+    pub title: String,
+    pub link_url: String,
+}
+
+impl From<&ArticleSidebarData> for ArticleLink {
+    fn from(article: &ArticleSidebarData) -> Self {
+        Self {
+            title: article.title.clone(),
+            link_url: article.link_url.clone(),
+        }
+    }
 }
 
 impl ArticleSidebarData {
     /// Generates the data needed for the sidebar from the yaml metadata block of the given article
     ///
+    /// The markdown source of the article is kept too.
     /// Will not set the `sub_articles` field.
-    pub fn from_article_meta(cfg: &DwwbConfig, md_path: &Path) -> Result<Self, String> {
-        let mut file = uw!(
-            File::open(md_path),
-            format!("opening file '{}'", md_path.display())
-        );
+    pub fn from_article_meta(cfg: &DwwbConfig, md_path: &Path) -> Result<Self> {
+        let mut file = File::open(md_path).map_err(DwwbError::io("opening the file", md_path))?;
         let mut contents = String::new();
-        uw!(
-            file.read_to_string(&mut contents),
-            format!("reading file '{}'", md_path.display())
-        );
+        file.read_to_string(&mut contents)
+            .map_err(DwwbError::io("reading the file", md_path))?;
+        let metadata_error = |source| DwwbError::Metadata {
+            path: md_path.to_path_buf(),
+            source,
+        };
 
         let mut html_path = cfg.outputs.root().to_path_buf();
         if let Some(parent) = cfg.outputs.articles_dir().parent() {
@@ -45,34 +101,41 @@ impl ArticleSidebarData {
         }
         let html_path = html_path.join(md_path).with_extension("html");
 
-        let r = Regex::new(r"(?msx)(?:\A|\r?\n\r?\n)(---\s*?$.*?)^(?:---|\.\.\.)\s*?$").unwrap();
-        let metadata_string = r
+        let metadata_string = METADATA_BLOCK_REGEX
             .captures(&contents)
             .and_then(|c| c.get(1)) // chop off the end lines/dots
-            .ok_or(format!(
-                "Expected a YAML metadata block at the start of the file '{}'",
-                md_path.display()
-            ))?
+            .ok_or_else(|| metadata_error(MetadataError::MissingBlock))?
             .as_str();
 
         let metadata: HashMap<String, serde_yaml::Value> =
-            serde_yaml::from_str(metadata_string).map_err(|e| format!("{e}"))?;
+            serde_yaml::from_str(metadata_string).map_err(|e| metadata_error(e.into()))?;
         if !metadata.contains_key("title") {
-            return Err(format!(
-                "No `title` in the YAML metadata block of the file '{}'",
-                md_path.display()
-            ));
+            return Err(metadata_error(MetadataError::MissingTitle));
         }
 
+        let weight = match ["weight", "order"]
+            .into_iter()
+            .find_map(|key| Some((key, metadata.get(key)?)))
+        {
+            Some((_, serde_yaml::Value::Number(n))) if n.is_i64() => n.as_i64(),
+            Some((key, val)) => {
+                return Err(metadata_error(MetadataError::WrongType {
+                    key,
+                    expected: "an integer",
+                    found: yaml_type_to_name(val),
+                }))
+            }
+            None => None,
+        };
+
         match &metadata["title"] {
             serde_yaml::Value::String(title) => Ok(Self {
-                id: md_path
-                    .file_stem()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string(),
+                id: Self::id_from_path(cfg, md_path),
                 title: title.to_string(),
-                link_url: url_escape::encode_fragment(&path_to_url(html_path.strip_prefix(cfg.outputs.root()).unwrap())).to_string(),
+                link_url: url_escape::encode_fragment(&path_to_url(
+                    html_path.strip_prefix(cfg.outputs.root()).unwrap(),
+                ))
+                .to_string(),
                 md_file_path: Some(md_path.to_path_buf()),
                 html_file_path: Some(html_path),
                 keywords: match metadata.get("keywords") {
@@ -82,22 +145,197 @@ impl ArticleSidebarData {
                         .map(str::to_string)
                         .collect(),
                     Some(val) => {
-                        return Err(format!(
-                            "Expected a YAML sequence as the `keywords` in the metadata of the file '{}', instead found {}",
-                            md_path.display(),
-                            yaml_type_to_name(val)
-                        ))
+                        return Err(metadata_error(MetadataError::WrongType {
+                            key: "keywords",
+                            expected: "a YAML sequence",
+                            found: yaml_type_to_name(val),
+                        }))
                     }
                     _ => vec![],
                 },
+                weight,
+                date: metadata.get("date").and_then(parse_date),
+                updated: metadata.get("updated").and_then(parse_date),
                 sub_articles: Default::default(),
+                generated_source: None,
+                source: Some(contents),
+                keyword_links: Vec::new(),
+                history: None,
+            }),
+            val => Err(metadata_error(MetadataError::WrongType {
+                key: "title",
+                expected: "a YAML string",
+                found: yaml_type_to_name(val),
+            })),
+        }
+    }
+
+    /// Sorts the sub-articles of the whole tree in the given order
+    ///
+    /// The ties are broken by the ids, so that the order doesn't depend on the order the files were found in.
+    pub fn sort_recursive(&mut self, sort: SidebarSort) {
+        let title_key = |article: &Self| article.title.to_lowercase();
+        match sort {
+            SidebarSort::Title => self.sub_articles.sort_by(|a, b| {
+                title_key(a)
+                    .cmp(&title_key(b))
+                    .then_with(|| a.id.cmp(&b.id))
+            }),
+            SidebarSort::Path => self.sub_articles.sort_by(|a, b| a.id.cmp(&b.id)),
+            SidebarSort::Weight => self.sub_articles.sort_by(|a, b| {
+                a.weight
+                    .unwrap_or(0)
+                    .cmp(&b.weight.unwrap_or(0))
+                    .then_with(|| title_key(a).cmp(&title_key(b)))
+                    .then_with(|| a.id.cmp(&b.id))
+            }),
+        }
+
+        for sub in &mut self.sub_articles {
+            sub.sort_recursive(sort);
+        }
+    }
+
+    /// Returns the id of the article with the given input path
+    ///
+    /// The id is the same as the id of the directory of its sub-articles, so that the two are merged.
+    pub fn id_from_path(cfg: &DwwbConfig, md_path: &Path) -> String {
+        let relative = match md_path.strip_prefix(cfg.inputs.articles_dir()) {
+            Ok(relative) => relative,
+            Err(_) => Path::new(md_path.file_name().unwrap_or_default()),
+        };
+        path_to_url(relative.with_extension(""))
+    }
+
+    /// Turns this into a generated article with the given markdown source
+    ///
+    /// The output file is placed as if the article were an input file with the same id.
+    pub fn set_generated(&mut self, cfg: &DwwbConfig, source: String) {
+        let mut html_path = cfg.outputs.root().to_path_buf();
+        if let Some(parent) = cfg.outputs.articles_dir().parent() {
+            html_path.push(parent);
+        }
+        html_path.push(cfg.inputs.articles_dir());
+        html_path.push(format!("{}.html", self.id));
+
+        self.link_url = url_escape::encode_fragment(&path_to_url(
+            html_path.strip_prefix(cfg.outputs.root()).unwrap(),
+        ))
+        .to_string();
+        self.html_file_path = Some(html_path);
+        self.generated_source = Some(source);
+    }
+
+    /// Checks that the given generated article doesn't have the same id as any of the articles in the tree
+    ///
+    /// The conflicting article or category would be written into the same file as the generated one.
+    pub fn check_generated_id(&self, cfg: &DwwbConfig, page: &Self) -> Result<()> {
+        match self.find(&page.id) {
+            Some(existing) => Err(DwwbError::IdConflict {
+                path: existing
+                    .md_file_path
+                    .clone()
+                    .unwrap_or_else(|| cfg.inputs.articles_dir().join(&existing.id)),
+                other: page.html_file_path.clone().unwrap_or_default(),
+                id: page.id.clone(),
             }),
-            val => Err(format!(
-                "Expected a YAML string as the `title` in the metadata of the file '{}', instead found {}",
-                md_path.display(),
-                yaml_type_to_name(val)
-            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns when the article was last modified
+    ///
+    /// That is the `updated` or the `date` metadata value, or without either,
+    /// the date of the latest commit of the markdown file or its modification time.
+    /// The generated articles have no modification time.
+    pub fn last_modified(&self) -> Option<DateTime<FixedOffset>> {
+        let committed = self.history.as_ref().map(|history| history.last_modified);
+        self.updated.or(self.date).or(committed).or_else(|| {
+            let modified = self
+                .md_file_path
+                .as_ref()?
+                .metadata()
+                .ok()?
+                .modified()
+                .ok()?;
+            Some(DateTime::<Utc>::from(modified).fixed_offset())
+        })
+    }
+
+    /// Returns the links to the articles from this one down to the article at the given path of sub-article indices
+    pub(crate) fn breadcrumbs(&self, path: &[usize]) -> Vec<ArticleLink> {
+        let mut node = self;
+        let mut crumbs = vec![ArticleLink::from(node)];
+        for &idx in path {
+            node = &node.sub_articles[idx];
+            crumbs.push(node.into());
         }
+        crumbs
+    }
+
+    /// Returns the links to the previous and the next sibling of the article at the given path of sub-article indices
+    ///
+    /// The categories without an article of their own are skipped.
+    /// The special pages, like the search page, are neither articles nor siblings of them, so they are skipped too
+    /// and have no previous or next article of their own.
+    pub(crate) fn prev_and_next(
+        &self,
+        path: &[usize],
+    ) -> (Option<ArticleLink>, Option<ArticleLink>) {
+        let (&idx, parent_path) = match path.split_last() {
+            Some(split) => split,
+            None => return (None, None),
+        };
+        let mut parent = self;
+        for &i in parent_path {
+            parent = &parent.sub_articles[i];
+        }
+        if parent.sub_articles[idx].is_special() {
+            return (None, None);
+        }
+
+        let has_page = |sibling: &&Self| !sibling.link_url.is_empty() && !sibling.is_special();
+        let prev = parent.sub_articles[..idx].iter().rev().find(has_page);
+        let next = parent.sub_articles[idx + 1..].iter().find(has_page);
+        (prev.map(Into::into), next.map(Into::into))
+    }
+
+    /// Returns whether this is one of the generated special pages in the reserved directory
+    pub fn is_special(&self) -> bool {
+        self.id
+            .strip_prefix(SPECIAL_PAGES_DIR)
+            .map_or(false, |rest| rest.starts_with('/'))
+    }
+
+    /// Returns an iterator over this article and all of its sub-articles, depth first
+    pub fn iter(&self) -> impl Iterator<Item = &Self> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.sub_articles.iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Returns the articles of the tree that have an input file, with their input files and markdown sources
+    pub(crate) fn input_articles(&self) -> impl Iterator<Item = (&Self, &Path, &str)> {
+        self.iter()
+            .filter_map(|node| Some((node, node.md_file_path.as_deref()?, node.source.as_deref()?)))
+    }
+
+    /// Calls the given function with this article and all of its sub-articles, depth first
+    ///
+    /// The mutable counterpart of [`ArticleSidebarData::iter`].
+    pub(crate) fn for_each_mut<F: FnMut(&mut Self)>(&mut self, f: &mut F) {
+        f(self);
+        for sub in &mut self.sub_articles {
+            sub.for_each_mut(f);
+        }
+    }
+
+    /// Returns the markdown source of the article, either the input file or the generated source
+    pub(crate) fn markdown_source(&self) -> Option<&str> {
+        self.source.as_deref().or(self.generated_source.as_deref())
     }
 
     /// Returns a reference to the sub-article with the given id if it exists
@@ -107,6 +345,21 @@ impl ArticleSidebarData {
             .find(|sub| sub.id == sub_article_id)
     }
 
+    /// Returns a reference to the article with the given id anywhere in the tree if it exists
+    ///
+    /// Only the sub-articles whose ids are the directories of the given id are searched.
+    pub fn find(&self, id: &str) -> Option<&ArticleSidebarData> {
+        self.sub_articles.iter().find_map(|sub| {
+            if sub.id == id {
+                Some(sub)
+            } else if id.strip_prefix(&sub.id)?.starts_with('/') {
+                sub.find(id)
+            } else {
+                None
+            }
+        })
+    }
+
     /// Returns a mutable reference to the sub-article with the given id if it exists
     pub fn get_mut(&mut self, sub_article_id: &str) -> Option<&mut ArticleSidebarData> {
         self.sub_articles
@@ -134,6 +387,90 @@ impl IndexMut<&str> for ArticleSidebarData {
     }
 }
 
+/// The tree of the articles for the sidebar of a single article
+///
+/// The article at the given path of sub-article indices has the `is-current` flag,
+/// and the articles along the path have the `is-ancestor` flag.
+/// The tree is serialized straight from the shared one, so that it isn't copied for every article.
+pub struct SidebarTree<'a> {
+    pub root: &'a ArticleSidebarData,
+    pub current: &'a [usize],
+}
+
+impl Serialize for SidebarTree<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        SidebarNode {
+            node: self.root,
+            current: Some(self.current),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// An article in the [`SidebarTree`], with the rest of the path to the current article if it's on the path
+struct SidebarNode<'a> {
+    node: &'a ArticleSidebarData,
+    current: Option<&'a [usize]>,
+}
+
+impl Serialize for SidebarNode<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let node = self.node;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("id", &node.id)?;
+        map.serialize_entry("title", &node.title)?;
+        map.serialize_entry("md-file-path", &node.md_file_path)?;
+        map.serialize_entry("html-file-path", &node.html_file_path)?;
+        map.serialize_entry("link-url", &node.link_url)?;
+        map.serialize_entry("keywords", &node.keywords)?;
+        if let Some(weight) = node.weight {
+            map.serialize_entry("weight", &weight)?;
+        }
+        match self.current {
+            Some([]) => map.serialize_entry("is-current", &true)?,
+            Some(_) => map.serialize_entry("is-ancestor", &true)?,
+            None => {}
+        }
+        let sub_articles: Vec<_> = node
+            .sub_articles
+            .iter()
+            .enumerate()
+            .map(|(i, sub)| SidebarNode {
+                node: sub,
+                current: self
+                    .current
+                    .and_then(|path| path.split_first())
+                    .filter(|(&idx, _)| idx == i)
+                    .map(|(_, rest)| rest),
+            })
+            .collect();
+        map.serialize_entry("sub-articles", &sub_articles)?;
+        map.end()
+    }
+}
+
+/// Returns the YAML metadata block of a generated article with the given title
+pub fn metadata_block(title: &str) -> String {
+    let metadata = serde_yaml::Mapping::from_iter([("title".into(), title.into())]);
+    // serializing a mapping of strings can't fail
+    format!(
+        "---\n{}---\n\n",
+        serde_yaml::to_string(&metadata).unwrap_or_default()
+    )
+}
+
+/// Parses a date metadata value, either a date like `2024-01-31` or an RFC 3339 date and time
+///
+/// The dates without a time are at midnight UTC.
+/// Other values, like the free-form dates that pandoc allows, are ignored.
+fn parse_date(val: &serde_yaml::Value) -> Option<DateTime<FixedOffset>> {
+    let s = val.as_str()?.trim();
+    DateTime::parse_from_rfc3339(s).ok().or_else(|| {
+        let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc().fixed_offset())
+    })
+}
+
 fn yaml_type_to_name(val: &serde_yaml::Value) -> &'static str {
     use serde_yaml::Value;
     match val {
@@ -146,3 +483,38 @@ fn yaml_type_to_name(val: &serde_yaml::Value) -> &'static str {
         Value::Tagged(_) => "a tagged value",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(id: &str, link_url: &str) -> ArticleSidebarData {
+        ArticleSidebarData {
+            id: id.to_string(),
+            title: id.to_string(),
+            link_url: link_url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn special_pages_are_not_siblings() {
+        let mut search = article("_special/search", "articles/_special/search.html");
+        search.generated_source = Some(String::new());
+        let root = ArticleSidebarData {
+            sub_articles: vec![
+                article("alice", "articles/alice.html"),
+                search,
+                article("people", ""),
+                article("bob", "articles/bob.html"),
+            ],
+            ..article("index", "index.html")
+        };
+        let link = |idx: usize| Some(ArticleLink::from(&root.sub_articles[idx]));
+
+        assert_eq!(root.prev_and_next(&[0]), (None, link(3)));
+        assert_eq!(root.prev_and_next(&[3]), (link(0), None));
+        assert_eq!(root.prev_and_next(&[1]), (None, None));
+        assert_eq!(root.prev_and_next(&[]), (None, None));
+    }
+}
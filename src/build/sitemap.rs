@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::SecondsFormat;
+
+use super::sidebar::ArticleSidebarData;
+use crate::config::DwwbConfig;
+use crate::error::{DwwbError, Result};
+use crate::util::escape_html;
+
+/// The file name of the sitemap, which is placed in the output root
+pub const SITEMAP_FILENAME: &str = "sitemap.xml";
+
+/// The file name of the robots file, which is placed in the output root
+pub const ROBOTS_FILENAME: &str = "robots.txt";
+
+/// Writes the sitemap and the robots file into the output root, if they are enabled in the configuration
+///
+/// The sitemap lists every article with a page, including the generated ones.
+///
+/// Returns the paths of the written files.
+pub fn write_sitemap(cfg: &DwwbConfig, root: &ArticleSidebarData) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    let site_url = cfg.site_url.as_ref().map(|url| site_url_with_slash(url));
+
+    if let Some(site_url) = &site_url {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+        for article in root
+            .iter()
+            .filter(|article| article.html_file_path.is_some())
+        {
+            xml.push_str(&format!(
+                "  <url>\n    <loc>{}</loc>\n",
+                escape_html(format!("{site_url}{}", article.link_url))
+            ));
+            if let Some(modified) = article.last_modified() {
+                xml.push_str(&format!(
+                    "    <lastmod>{}</lastmod>\n",
+                    modified.to_rfc3339_opts(SecondsFormat::Secs, true)
+                ));
+            }
+            xml.push_str("  </url>\n");
+        }
+        xml.push_str("</urlset>\n");
+
+        let path = cfg.outputs.root().join(SITEMAP_FILENAME);
+        fs::write(&path, xml).map_err(DwwbError::io("writing the sitemap", &path))?;
+        written.push(path);
+    }
+
+    if cfg.robots_txt {
+        let mut robots = String::from("User-agent: *\nAllow: /\n");
+        if let Some(site_url) = &site_url {
+            robots.push_str(&format!("\nSitemap: {site_url}{SITEMAP_FILENAME}\n"));
+        }
+
+        let path = cfg.outputs.root().join(ROBOTS_FILENAME);
+        fs::write(&path, robots).map_err(DwwbError::io("writing the robots file", &path))?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Returns the site url ending with a slash, so that the urls relative to the output root can be appended to it
+pub fn site_url_with_slash(site_url: &str) -> String {
+    if site_url.ends_with('/') {
+        site_url.to_string()
+    } else {
+        format!("{site_url}/")
+    }
+}
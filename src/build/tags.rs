@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+use super::sidebar::{metadata_block, ArticleLink, ArticleSidebarData};
+use crate::config::DwwbConfig;
+use crate::error::Result;
+use crate::util::{escape_html, escape_markdown};
+
+/// The id of the generated tags section, which is placed in the reserved `_special` directory of the articles
+pub const TAGS_ID: &str = "_special/tags";
+
+/// The number of the font sizes in the tag cloud
+const TAG_CLOUD_SIZES: usize = 5;
+
+/// The articles with the same keyword
+struct Tag {
+    /// The keyword as it was first written
+    name: String,
+    articles: Vec<ArticleLink>,
+}
+
+/// Generates the tags section from the keywords of the articles
+///
+/// The section has a page for every keyword, listing the articles with it,
+/// and the section page itself is a tag cloud of all the keywords.
+/// The articles get links to the pages of their keywords.
+/// The section isn't generated if no article has keywords.
+/// Fails if there already is an article or a category with the same id.
+pub fn generate_tag_pages(cfg: &DwwbConfig, root: &mut ArticleSidebarData) -> Result<()> {
+    let tags = collect_tags(root);
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    let mut section = ArticleSidebarData {
+        id: TAGS_ID.to_string(),
+        title: cfg.tags_title.clone(),
+        ..Default::default()
+    };
+    section.set_generated(cfg, String::new());
+    root.check_generated_id(cfg, &section)?;
+
+    let max_count = tags
+        .values()
+        .map(|tag| tag.articles.len())
+        .max()
+        .unwrap_or(1);
+    let mut cloud = String::from("<p class=\"tag-cloud\">\n");
+    let mut urls = BTreeMap::new();
+    for (slug, mut tag) in tags {
+        tag.articles
+            .sort_by_key(|article| article.title.to_lowercase());
+
+        let mut page = ArticleSidebarData {
+            id: format!("{TAGS_ID}/{slug}"),
+            title: tag.name.clone(),
+            ..Default::default()
+        };
+        page.set_generated(cfg, tag_page_source(&tag));
+
+        // the tag pages are in the directory next to the section page
+        let href = &page.link_url[section.link_url.rfind('/').map_or(0, |idx| idx + 1)..];
+        // the sizes are spread evenly from the rarest possible tag to the most common one
+        let size = 1 + (tag.articles.len() - 1) * (TAG_CLOUD_SIZES - 1) / (max_count - 1).max(1);
+        cloud.push_str(&format!(
+            "<a class=\"tag tag-size-{size}\" href=\"{}\">{}</a> <small>({})</small>\n",
+            escape_html(href),
+            escape_html(&tag.name),
+            tag.articles.len()
+        ));
+
+        urls.insert(slug, page.link_url.clone());
+        section.sub_articles.push(page);
+    }
+    cloud.push_str("</p>\n");
+
+    set_keyword_links(root, &urls);
+
+    section.generated_source = Some(metadata_block(&cfg.tags_title) + &cloud);
+    root.sub_articles.push(section);
+    Ok(())
+}
+
+/// Returns the markdown source of the page of the tag, which lists the articles with it
+fn tag_page_source(tag: &Tag) -> String {
+    let mut source = metadata_block(&escape_markdown(&tag.name));
+    for article in &tag.articles {
+        source.push_str(&format!(
+            "* [{}](%ROOT%{})\n",
+            escape_markdown(&article.title),
+            article.link_url
+        ));
+    }
+    source
+}
+
+/// Collects the keywords of all the articles in the tree by their slugs
+fn collect_tags(root: &ArticleSidebarData) -> BTreeMap<String, Tag> {
+    let mut tags = BTreeMap::new();
+    for node in root.iter().filter(|node| node.md_file_path.is_some()) {
+        for keyword in &node.keywords {
+            let slug = slug(keyword);
+            if slug.is_empty() {
+                continue;
+            }
+            let tag = tags.entry(slug).or_insert_with(|| Tag {
+                name: keyword.trim().to_string(),
+                articles: Vec::new(),
+            });
+            let link = ArticleLink::from(node);
+            // the same keyword can be written in different ways in the same article
+            if !tag.articles.contains(&link) {
+                tag.articles.push(link);
+            }
+        }
+    }
+    tags
+}
+
+/// Links the keywords of all the articles in the tree to their tag pages
+fn set_keyword_links(root: &mut ArticleSidebarData, urls: &BTreeMap<String, String>) {
+    root.for_each_mut(&mut |node| {
+        if node.md_file_path.is_none() {
+            return;
+        }
+        node.keyword_links = node
+            .keywords
+            .iter()
+            .filter_map(|keyword| {
+                Some(ArticleLink {
+                    title: keyword.trim().to_string(),
+                    link_url: urls.get(&slug(keyword))?.clone(),
+                })
+            })
+            .collect();
+    });
+}
+
+/// Turns the keyword into a lowercase file name of letters, numbers and dashes
+///
+/// The keywords that differ only by the case and the whitespace are the same tag.
+/// The keywords with other characters than letters, digits and spaces get a hash of the keyword
+/// after an underscore, like the anchors of the wanted pages, so that `C++` and `C#` don't share the page of `C`.
+/// The whitespace-only keywords have an empty slug.
+fn slug(keyword: &str) -> String {
+    let key = keyword
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    let mut slug = String::new();
+    let mut is_plain = true;
+    for c in key.chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else {
+            is_plain &= c == ' ';
+            if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+    }
+    let mut slug = slug.trim_end_matches('-').to_string();
+
+    if !is_plain {
+        let hash = Sha256::digest(key.as_bytes());
+        slug.push('_');
+        for byte in &hash[..4] {
+            slug.push_str(&format!("{byte:02x}"));
+        }
+    }
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_pages_are_escaped() {
+        let tag = Tag {
+            name: "*rust* | [lang]".to_string(),
+            articles: vec![ArticleLink {
+                title: "Alice_[1]".to_string(),
+                link_url: "articles/alice.html".to_string(),
+            }],
+        };
+        let source = tag_page_source(&tag);
+        assert!(source.starts_with(&metadata_block("\\*rust\\* \\| \\[lang\\]")));
+        assert!(source.ends_with("\n* [Alice\\_\\[1\\]](%ROOT%articles/alice.html)\n"));
+    }
+
+    #[test]
+    fn slugs_are_file_names() {
+        assert_eq!(slug("  Rust \t Lang "), "rust-lang");
+        assert_eq!(slug("rust lang"), slug("Rust Lang"));
+        assert_eq!(slug("C"), "c");
+        assert_eq!(slug(" \t "), "");
+
+        let slugs = [
+            "C",
+            "C++",
+            "C#",
+            "c--",
+            "Rust Lang",
+            "Rust-Lang",
+            "--",
+            "!!",
+        ]
+        .map(slug);
+        for (i, a) in slugs.iter().enumerate() {
+            assert!(!a.is_empty());
+            assert!(a
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_'));
+            for b in &slugs[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert!(slug("C++").starts_with("c_"));
+    }
+}
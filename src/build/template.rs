@@ -0,0 +1,576 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::error::DwwbError;
+
+/// The maximum nesting depth of the partials, same as in pandoc
+const MAX_PARTIAL_DEPTH: usize = 50;
+
+/// A template in the pandoc template syntax, used by the native renderer
+///
+/// Supports a subset of the syntax: variables, conditionals, loops with separators,
+/// partials, and comments. Pipes are ignored.
+///
+/// <https://pandoc.org/MANUAL.html#templates>
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// All of the partials used by the template and by the other partials
+    partials: HashMap<String, Vec<Node>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Variable(String),
+    /// A partial, optionally applied to a variable
+    Partial {
+        variable: Option<String>,
+        name: String,
+    },
+    Conditional {
+        /// The conditions and their contents, the `if` and all of the `elseif`s
+        branches: Vec<(String, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    Loop {
+        variable: String,
+        body: Vec<Node>,
+        separator: Vec<Node>,
+    },
+}
+
+/// The parts of the template before they are arranged into a tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Text(String),
+    Variable(String),
+    Partial {
+        variable: Option<String>,
+        name: String,
+    },
+    If(String),
+    ElseIf(String),
+    Else,
+    EndIf,
+    For(String),
+    Sep,
+    EndFor,
+}
+
+impl Template {
+    /// Loads the template and all of its partials
+    ///
+    /// The partials are looked up from the directory of the template.
+    /// If the name of a partial has no extension, the template's extension is used.
+    pub fn load(path: &Path) -> Result<Self, DwwbError> {
+        let source =
+            fs::read_to_string(path).map_err(DwwbError::io("reading the template", path))?;
+        Self::from_source(path, &source)
+    }
+
+    /// Loads the template like [`Template::load`], but uses the given source if the file doesn't exist
+    pub fn load_or_default(path: &Path, default: &str) -> Result<Self, DwwbError> {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::from_source(path, &source),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::from_source(path, default),
+            Err(e) => Err(DwwbError::io("reading the template", path)(e)),
+        }
+    }
+
+    /// Parses the template source and loads its partials from the directory of the given path
+    fn from_source(path: &Path, source: &str) -> Result<Self, DwwbError> {
+        let nodes = parse(source).map_err(|message| DwwbError::Template {
+            path: path.to_path_buf(),
+            message,
+        })?;
+
+        let mut template = Self {
+            nodes,
+            partials: HashMap::new(),
+        };
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let extension = path.extension().unwrap_or_default();
+
+        let mut unloaded = Vec::new();
+        collect_partial_names(&template.nodes, &mut unloaded);
+        while let Some(name) = unloaded.pop() {
+            if template.partials.contains_key(&name) {
+                continue;
+            }
+
+            let mut partial_path = dir.join(&name);
+            if partial_path.extension().is_none() {
+                partial_path.set_extension(extension);
+            }
+            let source = match fs::read_to_string(&partial_path) {
+                Ok(source) => source,
+                Err(_) if name == "styles.html" => {
+                    include_str!("../include/templates/styles.html").to_string()
+                }
+                Err(e) => {
+                    return Err(DwwbError::io("reading the partial", partial_path)(e));
+                }
+            };
+
+            let nodes = parse(&source).map_err(|message| DwwbError::Template {
+                path: partial_path,
+                message,
+            })?;
+            collect_partial_names(&nodes, &mut unloaded);
+            template.partials.insert(name, nodes);
+        }
+
+        Ok(template)
+    }
+
+    /// Renders the template with the given variables
+    pub fn render(&self, variables: &Mapping) -> Result<String, String> {
+        let mut output = String::new();
+        let mut ctx = Context {
+            root: variables,
+            scopes: Vec::new(),
+        };
+        self.render_nodes(&self.nodes, &mut ctx, 0, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_nodes<'t, 'a>(
+        &'t self,
+        nodes: &'t [Node],
+        ctx: &mut Context<'t, 'a>,
+        depth: usize,
+        output: &mut String,
+    ) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Variable(name) => {
+                    if let Some(value) = ctx.lookup(name) {
+                        render_value(value, output);
+                    }
+                }
+                Node::Partial { variable, name } => {
+                    if depth >= MAX_PARTIAL_DEPTH {
+                        return Err(format!(
+                            "The partials are nested too deep in the partial '{name}'"
+                        ));
+                    }
+                    let partial = &self.partials[name];
+
+                    match variable {
+                        None => self.render_nodes(partial, ctx, depth + 1, output)?,
+                        Some(variable) => {
+                            // a partial applied to a list is applied to each of its items
+                            let values = match ctx.lookup(variable) {
+                                Some(Value::Sequence(seq)) => seq.iter().collect(),
+                                Some(value) if is_truthy(value) => vec![value],
+                                _ => vec![],
+                            };
+                            for value in values {
+                                ctx.scopes.push(("it", value));
+                                let res = self.render_nodes(partial, ctx, depth + 1, output);
+                                ctx.scopes.pop();
+                                res?;
+                            }
+                        }
+                    }
+                }
+                Node::Conditional {
+                    branches,
+                    otherwise,
+                } => {
+                    let branch = branches
+                        .iter()
+                        .find(|(condition, _)| ctx.lookup(condition).map_or(false, is_truthy))
+                        .map(|(_, nodes)| nodes)
+                        .unwrap_or(otherwise);
+                    self.render_nodes(branch, ctx, depth, output)?;
+                }
+                Node::Loop {
+                    variable,
+                    body,
+                    separator,
+                } => {
+                    let values = match ctx.lookup(variable) {
+                        Some(Value::Sequence(seq)) => seq.iter().collect(),
+                        Some(value) if is_truthy(value) => vec![value],
+                        _ => vec![],
+                    };
+                    for (i, value) in values.into_iter().enumerate() {
+                        if i > 0 {
+                            self.render_nodes(separator, ctx, depth, output)?;
+                        }
+                        // the current item can be referred to by the variable name or by `it`
+                        ctx.scopes.push((variable, value));
+                        ctx.scopes.push(("it", value));
+                        let res = self.render_nodes(body, ctx, depth, output);
+                        ctx.scopes.truncate(ctx.scopes.len() - 2);
+                        res?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The variables available while rendering
+struct Context<'t, 'a> {
+    root: &'a Mapping,
+    /// The variables bound by the loops and the partials, the innermost last
+    scopes: Vec<(&'t str, &'a Value)>,
+}
+
+impl<'a> Context<'_, 'a> {
+    /// Finds the value of a possibly dotted variable name, like `sidebar-data.title`
+    fn lookup(&self, name: &str) -> Option<&'a Value> {
+        for (scope_name, value) in self.scopes.iter().rev() {
+            if name == *scope_name {
+                return Some(value);
+            }
+            if let Some(rest) = name
+                .strip_prefix(scope_name)
+                .and_then(|rest| rest.strip_prefix('.'))
+            {
+                return lookup_fields(value, rest);
+            }
+        }
+
+        let (first, rest) = match name.split_once('.') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (name, None),
+        };
+        let value = self.root.get(first)?;
+        match rest {
+            Some(rest) => lookup_fields(value, rest),
+            None => Some(value),
+        }
+    }
+}
+
+/// Finds the value of the dotted field path from the given value
+fn lookup_fields<'a>(mut value: &'a Value, fields: &str) -> Option<&'a Value> {
+    for field in fields.split('.') {
+        value = match value {
+            Value::Mapping(map) => map.get(field)?,
+            Value::Tagged(tagged) => lookup_fields(&tagged.value, field)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::String(s) => !s.is_empty(),
+        Value::Sequence(seq) => !seq.is_empty(),
+        Value::Mapping(map) => !map.is_empty(),
+        Value::Number(_) => true,
+        Value::Tagged(tagged) => is_truthy(&tagged.value),
+    }
+}
+
+fn render_value(value: &Value, output: &mut String) {
+    match value {
+        Value::Null | Value::Bool(false) => {}
+        Value::Bool(true) | Value::Mapping(_) => output.push_str("true"),
+        Value::Number(n) => output.push_str(&n.to_string()),
+        Value::String(s) => output.push_str(s),
+        Value::Sequence(seq) => seq.iter().for_each(|v| render_value(v, output)),
+        Value::Tagged(tagged) => render_value(&tagged.value, output),
+    }
+}
+
+fn collect_partial_names(nodes: &[Node], names: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Partial { name, .. } => names.push(name.clone()),
+            Node::Conditional {
+                branches,
+                otherwise,
+            } => {
+                for (_, nodes) in branches {
+                    collect_partial_names(nodes, names);
+                }
+                collect_partial_names(otherwise, names);
+            }
+            Node::Loop {
+                body, separator, ..
+            } => {
+                collect_partial_names(body, names);
+                collect_partial_names(separator, names);
+            }
+            Node::Text(_) | Node::Variable(_) => {}
+        }
+    }
+}
+
+/// Parses the template source into a tree of nodes
+fn parse(source: &str) -> Result<Vec<Node>, String> {
+    let mut tokens = tokenize(source)?.into_iter().peekable();
+    let nodes = parse_nodes(&mut tokens)?;
+    match tokens.next() {
+        None => Ok(nodes),
+        Some(token) => Err(format!("Unexpected {}", token_name(&token))),
+    }
+}
+
+/// Parses nodes until the end of the input or an unmatched closing keyword
+fn parse_nodes<I: Iterator<Item = Token>>(
+    tokens: &mut std::iter::Peekable<I>,
+) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.peek() {
+        if matches!(
+            token,
+            Token::ElseIf(_) | Token::Else | Token::EndIf | Token::Sep | Token::EndFor
+        ) {
+            break;
+        }
+
+        match tokens.next().unwrap() {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Variable(name) => nodes.push(Node::Variable(name)),
+            Token::Partial { variable, name } => nodes.push(Node::Partial { variable, name }),
+            Token::If(condition) => {
+                let mut branches = vec![(condition, parse_nodes(tokens)?)];
+                let mut otherwise = Vec::new();
+                loop {
+                    match tokens.next() {
+                        Some(Token::ElseIf(condition)) => {
+                            branches.push((condition, parse_nodes(tokens)?))
+                        }
+                        Some(Token::Else) => otherwise = parse_nodes(tokens)?,
+                        Some(Token::EndIf) => break,
+                        _ => return Err("Missing `$endif$`".to_string()),
+                    }
+                }
+                nodes.push(Node::Conditional {
+                    branches,
+                    otherwise,
+                });
+            }
+            Token::For(variable) => {
+                let body = parse_nodes(tokens)?;
+                let separator = if tokens.peek() == Some(&Token::Sep) {
+                    tokens.next();
+                    parse_nodes(tokens)?
+                } else {
+                    Vec::new()
+                };
+                if tokens.next() != Some(Token::EndFor) {
+                    return Err("Missing `$endfor$`".to_string());
+                }
+                nodes.push(Node::Loop {
+                    variable,
+                    body,
+                    separator,
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+    Ok(nodes)
+}
+
+/// Splits the template source into text and the `$...$` directives
+///
+/// The lines that contain nothing else but a keyword, like `$if(foo)$` or `$endfor$`, are removed completely.
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = source;
+    // whether there is nothing but whitespace on the current line before the current text
+    let mut line_is_empty = true;
+
+    while let Some(start) = rest.find('$') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            text.push('$');
+            rest = after;
+            continue;
+        }
+        if rest.starts_with("--") {
+            // a comment, skip until the end of the line
+            rest = rest.find('\n').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let end = rest.find('$').ok_or_else(|| "Unclosed `$`".to_string())?;
+        let token = parse_directive(&rest[..end])?;
+        rest = &rest[end + 1..];
+
+        let is_keyword = !matches!(
+            token,
+            Token::Text(_) | Token::Variable(_) | Token::Partial { .. }
+        );
+        let line_start = text.rfind('\n').map(|i| i + 1);
+        let line_end = rest.find('\n');
+        let alone_before = match line_start {
+            Some(line_start) => text[line_start..].trim().is_empty(),
+            None => line_is_empty && text.trim().is_empty(),
+        };
+        let alone_after = rest[..line_end.unwrap_or(rest.len())].trim().is_empty();
+
+        // remove the whole line if the keyword is alone on it
+        line_is_empty = is_keyword && alone_before && alone_after;
+        if line_is_empty {
+            text.truncate(line_start.unwrap_or(0));
+            rest = line_end.map_or("", |end| &rest[end + 1..]);
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(std::mem::take(&mut text)));
+        }
+        tokens.push(token);
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+
+    Ok(tokens)
+}
+
+fn parse_directive(directive: &str) -> Result<Token, String> {
+    // `${foo}` is the same as `$foo$`
+    let directive = directive
+        .strip_prefix('{')
+        .and_then(|d| d.strip_suffix('}'))
+        .unwrap_or(directive)
+        .trim();
+
+    let argument = |keyword: &str| {
+        directive
+            .strip_prefix(keyword)
+            .and_then(|d| d.strip_prefix('('))
+            .and_then(|d| d.strip_suffix(')'))
+            .map(|arg| arg.trim().to_string())
+    };
+
+    let token = if let Some(arg) = argument("if") {
+        Token::If(arg)
+    } else if let Some(arg) = argument("elseif") {
+        Token::ElseIf(arg)
+    } else if let Some(arg) = argument("for") {
+        Token::For(arg)
+    } else {
+        match directive {
+            "else" => Token::Else,
+            "endif" => Token::EndIf,
+            "sep" => Token::Sep,
+            "endfor" => Token::EndFor,
+            _ => {
+                if let Some(call) = directive.strip_suffix("()") {
+                    match call.split_once(':') {
+                        Some((variable, name)) => Token::Partial {
+                            variable: Some(variable.to_string()),
+                            name: name.to_string(),
+                        },
+                        None => Token::Partial {
+                            variable: None,
+                            name: call.to_string(),
+                        },
+                    }
+                } else {
+                    // the pipes are not supported, so they are just dropped
+                    let name = directive.split('/').next().unwrap_or_default();
+                    if name.is_empty()
+                        || !name
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                    {
+                        return Err(format!("Invalid template directive `${directive}$`"));
+                    }
+                    Token::Variable(name.to_string())
+                }
+            }
+        }
+    };
+    Ok(token)
+}
+
+fn token_name(token: &Token) -> &'static str {
+    match token {
+        Token::ElseIf(_) => "`$elseif$`",
+        Token::Else => "`$else$`",
+        Token::EndIf => "`$endif$`",
+        Token::Sep => "`$sep$`",
+        Token::EndFor => "`$endfor$`",
+        _ => "directive",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, variables: &str) -> String {
+        let template = Template::from_source(Path::new("template.html"), source).unwrap();
+        let variables: Mapping = serde_yaml::from_str(variables).unwrap();
+        template.render(&variables).unwrap()
+    }
+
+    #[test]
+    fn conditionals() {
+        let source = "$if(a)$A$elseif(b.c)$C$else$none$endif$";
+        assert_eq!(render(source, "a: yes"), "A");
+        assert_eq!(render(source, "b: { c: true }"), "C");
+        assert_eq!(render(source, "a: ''\nb: { c: false }"), "none");
+        assert_eq!(render(source, "a: []"), "none");
+    }
+
+    #[test]
+    fn keyword_lines_are_removed() {
+        let source = "<ul>\n$if(a)$\n<li>$a$</li>\n$endif$\n</ul>\n";
+        assert_eq!(render(source, "a: x"), "<ul>\n<li>x</li>\n</ul>\n");
+        assert_eq!(render(source, "{}"), "<ul>\n</ul>\n");
+    }
+
+    #[test]
+    fn loops_with_separators() {
+        let source = "$for(items)$$items.name$=$it.value$$sep$, $endfor$";
+        let variables =
+            "items: [{ name: a, value: 1 }, { name: b, value: 2 }, { name: c, value: 3 }]";
+        assert_eq!(render(source, variables), "a=1, b=2, c=3");
+        assert_eq!(render(source, "items: { name: a, value: 1 }"), "a=1");
+        assert_eq!(render(source, "items: []"), "");
+    }
+
+    #[test]
+    fn partial_falls_back_to_embedded_styles() {
+        // the directory doesn't exist, so the partial can't be read from it
+        let template =
+            Template::from_source(Path::new("/nonexistent/template.html"), "$styles.html()$")
+                .unwrap();
+        let output = template.render(&Mapping::new()).unwrap();
+        assert_eq!(
+            output,
+            render(include_str!("../include/templates/styles.html"), "{}")
+        );
+    }
+
+    #[test]
+    fn missing_partial_is_an_error() {
+        let result = Template::from_source(Path::new("/nonexistent/template.html"), "$other()$");
+        assert!(matches!(result, Err(DwwbError::Io { .. })));
+    }
+
+    #[test]
+    fn unclosed_directives_are_errors() {
+        assert!(parse("$if(a)$A").is_err());
+        assert!(parse("$for(a)$A$sep$").is_err());
+        assert!(parse("A$endif$").is_err());
+        assert!(parse("$a").is_err());
+    }
+}
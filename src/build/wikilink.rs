@@ -0,0 +1,605 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+
+use super::native::{heading_identifier, markdown_options};
+use super::sidebar::{metadata_block, ArticleLink, ArticleSidebarData, METADATA_BLOCK_REGEX};
+use crate::config::DwwbConfig;
+use crate::error::{DwwbError, Result};
+use crate::util::{escape_markdown, path_to_url};
+
+/// The id of the generated wanted pages article, which is placed in the articles directory
+///
+/// The `_special` directory is reserved for the generated pages, so that they don't take the ids of the articles.
+pub const WANTED_PAGES_ID: &str = "_special/wanted-pages";
+
+/// The CSS class of the links to the articles that don't exist
+const RED_LINK_CLASS: &str = "redlink";
+
+lazy_static! {
+    /// Matches the `[[Target]]` and `[[Target|label]]` links
+    ///
+    /// The target is in the first capture group and the optional label in the second.
+    static ref WIKI_LINK_REGEX: Regex =
+        Regex::new(r"\[\[([^\[\]|\n]+)(?:\|([^\[\]\n]+))?\]\]").unwrap();
+    static ref ROOT_URL_REGEX: Regex = Regex::new("^%ROOT%/?").unwrap();
+}
+
+/// Resolves the `[[Title]]` and `[[Title|label]]` links of the articles
+///
+/// The links are matched against the titles and the ids of all the articles in the tree,
+/// ignoring the case and the surrounding whitespace. The titles take precedence over the ids.
+///
+/// The wiki links and the `%ROOT%` links to the articles that don't exist are turned into red links.
+#[derive(Debug, Clone, Default)]
+pub struct WikiLinks {
+    titles: HashMap<String, Vec<LinkTarget>>,
+    ids: HashMap<String, Vec<LinkTarget>>,
+    /// The decoded urls of all the articles, relative to the output root
+    urls: HashSet<String>,
+    /// The url of the articles directory, relative to the output root
+    ///
+    /// Only the `%ROOT%` links inside it are checked.
+    articles_url: String,
+    /// The url of the wanted pages article, relative to the output root
+    wanted_url: String,
+}
+
+/// An article that the wiki links can point to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LinkTarget {
+    link_url: String,
+    /// The markdown file of the article, or the html file if the article is generated
+    path: PathBuf,
+}
+
+impl From<&ArticleSidebarData> for LinkTarget {
+    fn from(article: &ArticleSidebarData) -> Self {
+        Self {
+            link_url: article.link_url.clone(),
+            path: article
+                .md_file_path
+                .clone()
+                .or_else(|| article.html_file_path.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A link target that doesn't match any article
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MissingTarget {
+    /// The normalized target, which identifies the same target written in different ways
+    pub key: String,
+    /// The target as it was written in the link
+    pub name: String,
+}
+
+impl MissingTarget {
+    /// Returns the identifier of the target's heading in the wanted pages article
+    ///
+    /// The targets with other characters than letters, digits and spaces get a hash of the target
+    /// after an underscore, so that the targets that differ only by those characters get different identifiers.
+    pub fn anchor(&self) -> String {
+        let mut anchor = String::from("wanted-");
+        let mut is_plain = true;
+        for c in self.key.chars() {
+            if c.is_alphanumeric() {
+                anchor.push(c);
+            } else {
+                is_plain &= c == ' ';
+                if !anchor.ends_with('-') {
+                    anchor.push('-');
+                }
+            }
+        }
+        let mut anchor = anchor.trim_end_matches('-').to_string();
+
+        if !is_plain {
+            let hash = Sha256::digest(self.key.as_bytes());
+            anchor.push('_');
+            for byte in &hash[..4] {
+                anchor.push_str(&format!("{byte:02x}"));
+            }
+        }
+        anchor
+    }
+}
+
+impl WikiLinks {
+    /// Collects the link targets from the given article tree
+    ///
+    /// The categories without an article of their own can't be linked to.
+    pub fn new(cfg: &DwwbConfig, root: &ArticleSidebarData) -> Self {
+        let mut articles_dir = cfg
+            .outputs
+            .articles_dir()
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        articles_dir.push(cfg.inputs.articles_dir());
+
+        let articles_url = path_to_url(articles_dir);
+        let mut links = Self {
+            wanted_url: url_escape::encode_fragment(&format!(
+                "{articles_url}/{WANTED_PAGES_ID}.html"
+            ))
+            .into_owned(),
+            articles_url,
+            ..Default::default()
+        };
+        links.insert(root);
+        links
+    }
+
+    /// Adds the given article and all of its sub-articles as link targets
+    ///
+    /// Used for the articles that are added to the tree after the links have been collected.
+    pub fn insert(&mut self, root: &ArticleSidebarData) {
+        for node in root.iter() {
+            if node.md_file_path.is_some() {
+                self.titles
+                    .entry(normalize(&node.title))
+                    .or_default()
+                    .push(node.into());
+                self.ids
+                    .entry(normalize(&node.id))
+                    .or_default()
+                    .push(node.into());
+                // the articles in the directories can also be linked to by their file names
+                if let Some((_, name)) = node.id.rsplit_once('/') {
+                    self.ids
+                        .entry(normalize(name))
+                        .or_default()
+                        .push(node.into());
+                }
+            } else if node.generated_source.is_some() {
+                // the generated pages, like the category and the tag pages, can be linked to only by their full ids,
+                // so that they don't make the links to the articles ambiguous
+                self.ids
+                    .entry(normalize(&node.id))
+                    .or_default()
+                    .push(node.into());
+            }
+            if node.html_file_path.is_some() {
+                self.urls
+                    .insert(url_escape::decode(&node.link_url).into_owned());
+            }
+        }
+    }
+
+    /// Returns the article that the link target refers to, or `None` if there is no such article
+    ///
+    /// The error is the reason why the target couldn't be resolved.
+    fn resolve(&self, target: &str) -> Result<Option<&LinkTarget>, String> {
+        let key = normalize(target);
+        let matches = match self.titles.get(&key).or_else(|| self.ids.get(&key)) {
+            Some(matches) => matches,
+            None => return Ok(None),
+        };
+
+        match matches.as_slice() {
+            [article] => Ok(Some(article)),
+            _ => Err(format!(
+                "it matches multiple articles: {}",
+                matches
+                    .iter()
+                    .map(|article| format!("'{}'", article.path.display()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// Rewrites the wiki links of the markdown source into ordinary markdown links
+    ///
+    /// The links are made relative with the given url of the output root.
+    /// The links to the missing articles are rewritten into red links.
+    /// The links inside the metadata block and the code are left as they are.
+    pub fn replace_links(&self, md_path: &Path, source: &str, root_url: &str) -> Result<String> {
+        let source = self.mark_missing_root_links(source, &mut Vec::new());
+        self.replace_wiki_links(md_path, &source, root_url, &mut Vec::new())
+    }
+
+    /// Returns the targets of the links in the markdown source that don't match any article
+    ///
+    /// The invalid links are ignored, as they are reported when the article is built.
+    pub fn missing_targets(&self, md_path: &Path, source: &str) -> Vec<MissingTarget> {
+        let mut missing = Vec::new();
+        let source = self.mark_missing_root_links(source, &mut missing);
+        // only the targets are needed
+        let _ = self.replace_wiki_links(md_path, &source, "", &mut missing);
+        missing
+    }
+
+    fn replace_wiki_links(
+        &self,
+        md_path: &Path,
+        source: &str,
+        root_url: &str,
+        missing: &mut Vec<MissingTarget>,
+    ) -> Result<String> {
+        let skipped = skipped_ranges(source);
+        let mut error = None;
+
+        let replaced = WIKI_LINK_REGEX.replace_all(source, |caps: &Captures| {
+            let link = caps.get(0).unwrap();
+            if error.is_some() || skipped.iter().any(|range| range.contains(&link.start())) {
+                return link.as_str().to_string();
+            }
+
+            let target = &caps[1];
+            // the fragment is turned into a heading identifier, so that the links can point to the headings by their text
+            let (title, fragment) = match target.split_once('#') {
+                Some((title, fragment)) if !fragment.trim().is_empty() => {
+                    (title, format!("#{}", heading_identifier(fragment)))
+                }
+                Some((title, _)) => (title, String::new()),
+                None => (target, String::new()),
+            };
+            let label = caps.get(2).map_or(title, |label| label.as_str()).trim();
+
+            match self.resolve(title) {
+                Ok(Some(article)) => {
+                    format!("[{label}]({root_url}{}{fragment})", article.link_url)
+                }
+                Ok(None) => {
+                    // the red links point to the target's entry in the wanted pages article
+                    let target = MissingTarget {
+                        key: normalize(title),
+                        name: title.trim().to_string(),
+                    };
+                    let link = format!(
+                        "<a class=\"{RED_LINK_CLASS}\" href=\"{root_url}{}#{}\">{label}</a>",
+                        self.wanted_url,
+                        target.anchor()
+                    );
+                    missing.push(target);
+                    link
+                }
+                Err(reason) => {
+                    error = Some(DwwbError::WikiLink {
+                        path: md_path.to_path_buf(),
+                        target: target.to_string(),
+                        reason,
+                    });
+                    link.as_str().to_string()
+                }
+            }
+        });
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(replaced.into_owned()),
+        }
+    }
+
+    /// Adds the red link class to the `%ROOT%` links that point to missing articles
+    ///
+    /// The links are rewritten into html, as markdown has no syntax for the classes of the links.
+    fn mark_missing_root_links(&self, source: &str, missing: &mut Vec<MissingTarget>) -> String {
+        let mut output = String::with_capacity(source.len());
+        let mut copied_up_to = 0;
+
+        let mut events = Parser::new_ext(source, markdown_options()).into_offset_iter();
+        while let Some((event, range)) = events.next() {
+            let url = match event {
+                Event::Start(Tag::Link(_, url, _)) if range.start >= copied_up_to => url,
+                _ => continue,
+            };
+            let target = match self.missing_root_target(&url) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            // the label of the link is between its first and last inner events
+            let mut label_range: Option<Range<usize>> = None;
+            for (event, inner) in events.by_ref() {
+                if let Event::End(Tag::Link(..)) = event {
+                    break;
+                }
+                label_range = Some(match label_range {
+                    Some(label_range) => label_range.start..inner.end.max(label_range.end),
+                    None => inner,
+                });
+            }
+            let label = label_range.map_or("", |label_range| &source[label_range]);
+
+            output.push_str(&source[copied_up_to..range.start]);
+            output.push_str(&format!(
+                "<a class=\"{RED_LINK_CLASS}\" href=\"{url}\">{label}</a>"
+            ));
+            copied_up_to = range.end;
+            missing.push(target);
+        }
+
+        output.push_str(&source[copied_up_to..]);
+        output
+    }
+
+    /// Returns the decoded urls of the articles that the markdown source links to, relative to the output root
+    ///
+    /// Covers the wiki links, the `%ROOT%` links and the links relative to the given decoded url of the article.
+    /// The links of the article to itself are left out.
+    pub fn linked_articles(
+        &self,
+        md_path: &Path,
+        source: &str,
+        article_url: &str,
+    ) -> HashSet<String> {
+        // resolving the wiki links against the output root turns them into `%ROOT%` links
+        let source = match self.replace_wiki_links(md_path, source, "%ROOT%", &mut Vec::new()) {
+            Ok(source) => source,
+            // the invalid links are reported when the article is built
+            Err(_) => return HashSet::new(),
+        };
+
+        Parser::new_ext(&source, markdown_options())
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link(_, url, _)) => resolve_url(&url, article_url),
+                _ => None,
+            })
+            .filter(|url| url != article_url && self.urls.contains(url))
+            .collect()
+    }
+
+    /// Returns the missing article that the url points to, if it is a `%ROOT%` link to one
+    fn missing_root_target(&self, url: &str) -> Option<MissingTarget> {
+        let relative = ROOT_URL_REGEX.find(url)?;
+        let path = url[relative.end()..].split(['#', '?']).next()?;
+        let path = url_escape::decode(path);
+
+        let in_articles = path
+            .strip_prefix(self.articles_url.as_str())
+            .map_or(false, |rest| rest.starts_with('/'));
+        if !in_articles || !path.ends_with(".html") || self.urls.contains(path.as_ref()) {
+            return None;
+        }
+        Some(MissingTarget {
+            key: path.to_lowercase(),
+            name: path.into_owned(),
+        })
+    }
+}
+
+/// A missing link target and the articles that link to it
+#[derive(Debug, Clone)]
+pub struct WantedPage {
+    /// The target as it was written in the first link to it
+    pub target: MissingTarget,
+    /// The titles and the urls of the articles that link to the target
+    pub linked_from: Vec<(String, String)>,
+}
+
+/// Finds the missing targets of the links in all of the articles in the tree
+///
+/// Returns the wanted pages by their normalized targets.
+pub fn find_wanted_pages(
+    links: &WikiLinks,
+    root: &ArticleSidebarData,
+) -> BTreeMap<String, WantedPage> {
+    let mut wanted = BTreeMap::new();
+    for (node, md_path, source) in root.input_articles() {
+        for target in links.missing_targets(md_path, source) {
+            wanted
+                .entry(target.key.clone())
+                .or_insert_with(|| WantedPage {
+                    target,
+                    linked_from: Vec::new(),
+                })
+                .linked_from
+                .push((node.title.clone(), node.link_url.clone()));
+        }
+    }
+    for page in wanted.values_mut() {
+        page.linked_from.sort();
+        page.linked_from.dedup();
+    }
+    wanted
+}
+
+/// Finds the articles that link to each of the articles in the tree
+///
+/// Returns the backlinks by the decoded urls of the linked articles, sorted by the titles.
+pub fn find_backlinks(
+    links: &WikiLinks,
+    root: &ArticleSidebarData,
+) -> HashMap<String, Vec<ArticleLink>> {
+    let mut backlinks = HashMap::<_, Vec<_>>::new();
+    for (node, md_path, source) in root.input_articles() {
+        let article_url = url_escape::decode(&node.link_url);
+        for url in links.linked_articles(md_path, source, &article_url) {
+            backlinks.entry(url).or_default().push(node.into());
+        }
+    }
+    for list in backlinks.values_mut() {
+        list.sort();
+        list.dedup();
+    }
+    backlinks
+}
+
+/// Generates the markdown source of the wanted pages article
+///
+/// Every missing target gets a heading with the anchor that the red links point to,
+/// and a list of the articles that link to it.
+/// The targets and the titles are escaped, so that they are shown as they were written.
+pub fn wanted_pages_source(title: &str, wanted: &BTreeMap<String, WantedPage>) -> String {
+    let mut source = metadata_block(title);
+
+    for page in wanted.values() {
+        source.push_str(&format!(
+            "\n## {} {{#{}}}\n\n",
+            escape_markdown(&page.target.name),
+            page.target.anchor()
+        ));
+        for (title, url) in &page.linked_from {
+            source.push_str(&format!("* [{}](%ROOT%{url})\n", escape_markdown(title)));
+        }
+    }
+    source
+}
+
+/// Returns the byte ranges of the metadata block and the code in the markdown source
+fn skipped_ranges(source: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<_> = METADATA_BLOCK_REGEX
+        .find_iter(source)
+        .map(|m| m.range())
+        .collect();
+
+    ranges.extend(
+        Parser::new_ext(source, markdown_options())
+            .into_offset_iter()
+            .filter(|(event, _)| matches!(event, Event::Code(_) | Event::Start(Tag::CodeBlock(_))))
+            .map(|(_, range)| range),
+    );
+    ranges
+}
+
+/// Resolves the url of a link into a decoded url relative to the output root
+///
+/// The relative urls are resolved against the given decoded url of the article.
+/// Returns `None` for the absolute urls and the links within the same page.
+fn resolve_url(url: &str, article_url: &str) -> Option<String> {
+    let path = url.split(['#', '?']).next()?;
+    let path = url_escape::decode(path);
+
+    let (mut segments, path) = match ROOT_URL_REGEX.find(&path) {
+        Some(root) => (Vec::new(), &path[root.end()..]),
+        None if path.is_empty() || path.starts_with('/') || path.contains(':') => return None,
+        None => {
+            let mut segments: Vec<_> = article_url.split('/').collect();
+            // the article's own file name
+            segments.pop();
+            (segments, path.as_ref())
+        }
+    };
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+/// Normalizes a title or an id for matching the links
+fn normalize(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> ArticleSidebarData {
+        let alice = ArticleSidebarData {
+            id: "people/alice".to_string(),
+            title: "Alice".to_string(),
+            md_file_path: Some("articles/people/alice.md".into()),
+            html_file_path: Some("html/articles/people/alice.html".into()),
+            link_url: "articles/people/alice.html".to_string(),
+            ..Default::default()
+        };
+        ArticleSidebarData {
+            id: "index".to_string(),
+            title: "Index".to_string(),
+            sub_articles: vec![alice],
+            ..Default::default()
+        }
+    }
+
+    fn replace(source: &str) -> String {
+        let cfg = DwwbConfig::default();
+        let root = tree();
+        let links = WikiLinks::new(&cfg, &root);
+        links
+            .replace_links(Path::new("index.md"), source, "../")
+            .unwrap()
+    }
+
+    #[test]
+    fn links_by_title_and_id() {
+        assert_eq!(
+            replace("[[alice]]"),
+            "[alice](../articles/people/alice.html)"
+        );
+        assert_eq!(
+            replace("[[People/Alice|my friend]]"),
+            "[my friend](../articles/people/alice.html)"
+        );
+    }
+
+    #[test]
+    fn heading_links_with_spaces() {
+        assert_eq!(
+            replace("[[alice#Early life]]"),
+            "[alice](../articles/people/alice.html#early-life)"
+        );
+        assert_eq!(
+            replace("[[Alice#early-life|her youth]]"),
+            "[her youth](../articles/people/alice.html#early-life)"
+        );
+    }
+
+    #[test]
+    fn missing_targets_are_red_links() {
+        assert_eq!(
+            replace("[[Bob#Early life]]"),
+            "<a class=\"redlink\" href=\"../articles/_special/wanted-pages.html#wanted-bob\">Bob</a>"
+        );
+    }
+
+    #[test]
+    fn wanted_anchors_are_unique() {
+        let anchor = |key: &str| {
+            MissingTarget {
+                key: key.to_string(),
+                name: key.to_string(),
+            }
+            .anchor()
+        };
+        assert_eq!(anchor("early life"), "wanted-early-life");
+        let anchors: HashSet<_> = ["early life", "early-life", "early life?", "early/life"]
+            .into_iter()
+            .map(anchor)
+            .collect();
+        assert_eq!(anchors.len(), 4);
+        assert!(anchors.iter().all(|a| a.starts_with("wanted-early-life")));
+    }
+
+    #[test]
+    fn wanted_pages_are_escaped() {
+        let target = MissingTarget {
+            key: "*bob* | [the builder]".to_string(),
+            name: "*Bob* | [the builder]".to_string(),
+        };
+        let wanted = BTreeMap::from([(
+            target.key.clone(),
+            WantedPage {
+                linked_from: vec![("Alice_[1]".to_string(), "articles/alice.html".to_string())],
+                target,
+            },
+        )]);
+        let source = wanted_pages_source("Wanted", &wanted);
+        assert!(source.contains("\n## \\*Bob\\* \\| \\[the builder\\] {#wanted-"));
+        assert!(source.contains("\n* [Alice\\_\\[1\\]](%ROOT%articles/alice.html)\n"));
+    }
+
+    #[test]
+    fn code_is_skipped() {
+        assert_eq!(replace("`[[alice]]`"), "`[[alice]]`");
+    }
+}
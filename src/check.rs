@@ -0,0 +1,92 @@
+use std::io;
+use std::path::Path;
+
+use crate::build::{read_article_tree, ArticleTree, Template};
+use crate::config::{DwwbConfig, Renderer};
+use crate::error::DwwbError;
+
+/// Performs the `check` command
+///
+/// Validates the project without writing any output.
+/// Reads the articles into a tree, generates the pages, resolves the wiki links and loads the templates
+/// the same way as the build, but doesn't stop at the first problem.
+///
+/// Returns all of the problems found.
+pub fn check_project(cfg: &DwwbConfig) -> Vec<DwwbError> {
+    let mut problems = Vec::new();
+
+    let mut check_file = |path: &Path, action: &'static str| {
+        if !path.is_file() {
+            problems.push(DwwbError::io(action, path)(io::ErrorKind::NotFound.into()));
+        }
+    };
+    check_file(cfg.inputs.style(), "finding the stylesheet");
+    check_file(
+        cfg.inputs.article_template(),
+        "finding the article template",
+    );
+
+    // pandoc parses its own templates
+    if cfg.renderer == Renderer::Native && cfg.inputs.article_template().is_file() {
+        if let Err(e) = Template::load(cfg.inputs.article_template()) {
+            problems.push(e);
+        }
+    }
+
+    for (_, glob) in cfg.inputs.non_articles_glob_iter() {
+        if let Err(e) = glob.to_glob_walker_builder().build() {
+            problems.push(DwwbError::glob(&glob.base)(e));
+        }
+    }
+
+    let ArticleTree { root, links } = match read_article_tree(cfg, true, &mut problems) {
+        Ok(tree) => tree,
+        Err(e) => {
+            problems.push(e);
+            return problems;
+        }
+    };
+
+    for (_, md_path, source) in root.input_articles() {
+        if let Err(e) = links.replace_links(md_path, source, "") {
+            problems.push(e);
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn every_broken_article_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let index = root.join("index.md");
+        let bad = root.join("articles/bad.md");
+        fs::create_dir(root.join("articles")).unwrap();
+        fs::write(&index, "No metadata\n").unwrap();
+        fs::write(&bad, "---\nkeywords: []\n---\n").unwrap();
+        fs::write(root.join("articles/good.md"), "---\ntitle: Good\n---\n").unwrap();
+
+        // the paths are absolute, so that the check doesn't depend on the working directory
+        let path = |path: &Path| serde_yaml::Value::from(path.to_string_lossy());
+        let mut cfg = serde_yaml::to_value(DwwbConfig::default()).unwrap();
+        cfg["inputs"]["index"] = path(&index);
+        cfg["inputs"]["articles"]["base"] = path(&root.join("articles"));
+        cfg["outputs"]["root"] = path(root);
+        let cfg: DwwbConfig = serde_yaml::from_value(cfg).unwrap();
+
+        let problems = check_project(&cfg);
+        let broken: Vec<_> = problems
+            .iter()
+            .filter(|e| matches!(e, DwwbError::Metadata { .. }))
+            .filter_map(DwwbError::path)
+            .collect();
+        assert_eq!(broken, [index.as_path(), bad.as_path()]);
+    }
+}
@@ -5,9 +5,10 @@ use std::path::PathBuf;
 use std::{collections::BTreeMap, path::Path};
 
 use globwalk::GlobWalkerBuilder;
+use ignore::overrides::{Override, OverrideBuilder};
 use serde::{Deserialize, Serialize};
 
-use crate::uw;
+use crate::error::{ConfigError, DwwbError, Result};
 
 pub const CFG_FILENAME: &str = "dwwb.yaml";
 
@@ -21,8 +22,45 @@ pub struct DwwbConfig {
     pub sub_articles_title: String,
     pub toc_title: String,
     pub toc_depth: u32,
+    /// The title of the list of the articles that link to the current article
+    #[serde(default = "default_backlinks_title")]
+    pub backlinks_title: String,
+    /// The title of the generated tag cloud page of the tags section
+    #[serde(default = "default_tags_title")]
+    pub tags_title: String,
+    /// The title of the generated search page and the placeholder of the search box
+    #[serde(default = "default_search_title")]
+    pub search_title: String,
+    /// The title of the generated page that lists the latest commits of the git repository
+    #[serde(default = "default_recent_changes_title")]
+    pub recent_changes_title: String,
+    /// The title of the generated article that lists the links to the missing articles
+    #[serde(default = "default_wanted_pages_title")]
+    pub wanted_pages_title: String,
+    /// The order of the articles in the sidebar
+    #[serde(default)]
+    pub sidebar_sort: SidebarSort,
+    /// The backend used for converting the articles into html
+    #[serde(default)]
+    pub renderer: Renderer,
     #[serde(default)]
     pub math_renderer: Option<MathRenderer>,
+    /// The public url of the output root, like `https://example.com/wiki/`
+    ///
+    /// The sitemap is only generated if this is set, as it needs absolute urls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site_url: Option<String>,
+    /// Whether to write a `robots.txt` file into the output root
+    #[serde(default)]
+    pub robots_txt: bool,
+    /// The feed of the recently changed articles, which isn't generated without this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feed: Option<FeedConfig>,
+    /// The number of articles to convert in parallel
+    ///
+    /// Defaults to the number of available CPU cores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
     /// A debug option to print out pandoc's output
     #[serde(default)]
     pub debug_pandoc_cmd: bool,
@@ -30,7 +68,7 @@ pub struct DwwbConfig {
 
 impl DwwbConfig {
     /// Returns the `dwwb.yaml` configuration file from the given directory, or the current working directory if not given.
-    pub fn from_file(root: Option<PathBuf>) -> Result<DwwbConfig, String> {
+    pub fn from_file(root: Option<PathBuf>) -> Result<DwwbConfig> {
         let path = if let Some(mut root) = root {
             root.push(CFG_FILENAME);
             root
@@ -38,50 +76,110 @@ impl DwwbConfig {
             PathBuf::from(CFG_FILENAME)
         };
 
+        let config_error = |source| DwwbError::Config {
+            path: path.clone(),
+            source,
+        };
+
         if !path.exists() {
-            return Err(format!("No configuration file '{CFG_FILENAME}' found!"));
+            return Err(config_error(ConfigError::NotFound));
         }
 
-        let cfg = uw!(File::open(path), "reading the configuration file");
-        let cfg: Self = uw!(
-            serde_yaml::from_reader(cfg),
-            "deserializing the configuration file"
-        );
+        let cfg = File::open(&path).map_err(|e| config_error(e.into()))?;
+        let cfg: Self = serde_yaml::from_reader(cfg).map_err(|e| config_error(e.into()))?;
 
-        cfg.validate()?;
+        cfg.validate().map_err(config_error)?;
         Ok(cfg)
     }
 
-    /// Checks if this is a valid config and returns an error message otherwise
+    /// Checks if this is a valid config and returns the invalid value otherwise
     ///
     /// Automatically called in the `from_file` method.
     ///
     // TODO: add more checks
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Invalid(msg.to_string()));
+
         if self.name.is_empty() {
-            return Err("Name of the project cannot be empty in `dwwb.yaml`".to_string());
+            return invalid("Name of the project cannot be empty");
         }
         if self.sub_articles_title.is_empty() {
-            return Err("`sub_articles_title` cannot be empty in `dwwb.yaml`".to_string());
+            return invalid("`sub_articles_title` cannot be empty");
         }
         if self.toc_title.is_empty() {
-            return Err("`toc_title` cannot be empty in `dwwb.yaml`".to_string());
+            return invalid("`toc_title` cannot be empty");
+        }
+        if self.backlinks_title.is_empty() {
+            return invalid("`backlinks-title` cannot be empty");
+        }
+        if self.tags_title.is_empty() {
+            return invalid("`tags-title` cannot be empty");
+        }
+        if self.search_title.is_empty() {
+            return invalid("`search-title` cannot be empty");
+        }
+        if self.recent_changes_title.is_empty() {
+            return invalid("`recent-changes-title` cannot be empty");
+        }
+        if self.wanted_pages_title.is_empty() {
+            return invalid("`wanted-pages-title` cannot be empty");
         }
         if self.inputs.index.file_name().is_none() {
-            return Err("`inputs.index` must have a name in `dwwb.yaml`".to_string());
+            return invalid("`inputs.index` must have a name");
+        }
+        if self.renderer == Renderer::Native && self.math_renderer.is_some() {
+            return invalid("`math-renderer` is not supported by the native renderer");
+        }
+        if let Some(site_url) = &self.site_url {
+            if !site_url.starts_with("http://") && !site_url.starts_with("https://") {
+                return invalid("`site-url` must be an absolute `http://` or `https://` url");
+            }
+        }
+        if let Some(feed) = &self.feed {
+            if self.site_url.is_none() {
+                return invalid(
+                    "`feed` needs the `site-url`, as the feeds must have absolute urls",
+                );
+            }
+            if feed.entries == 0 {
+                return invalid("`feed.entries` must be at least 1");
+            }
+        }
+        if self.jobs == Some(0) {
+            return invalid("`jobs` must be at least 1");
         }
         if self.outputs.root.file_name().is_none() {
-            return Err("`outputs.root` must have a name in `dwwb.yaml`".to_string());
+            return invalid("`outputs.root` must have a name");
         }
 
         // check if the outputs has matching keys for the arbitrary inputs
         if self.inputs.others.keys().ne(self.outputs.others.keys()) {
-            return Err("The inputs must match the outputs in `dwwb.yaml`".to_string());
+            return invalid("The inputs must match the outputs");
         }
         Ok(())
     }
 }
 
+fn default_backlinks_title() -> String {
+    "What Links Here".to_string()
+}
+
+fn default_tags_title() -> String {
+    "Tags".to_string()
+}
+
+fn default_search_title() -> String {
+    "Search".to_string()
+}
+
+fn default_recent_changes_title() -> String {
+    "Recent Changes".to_string()
+}
+
+fn default_wanted_pages_title() -> String {
+    "Wanted Pages".to_string()
+}
+
 impl Default for DwwbConfig {
     fn default() -> Self {
         Self {
@@ -92,7 +190,18 @@ impl Default for DwwbConfig {
             sub_articles_title: "Sub-Articles".to_string(),
             toc_title: "Table of Contents".to_string(),
             toc_depth: 3,
+            backlinks_title: default_backlinks_title(),
+            tags_title: default_tags_title(),
+            search_title: default_search_title(),
+            recent_changes_title: default_recent_changes_title(),
+            wanted_pages_title: default_wanted_pages_title(),
+            sidebar_sort: SidebarSort::Path,
+            renderer: Renderer::Pandoc,
             math_renderer: None,
+            site_url: None,
+            robots_txt: false,
+            feed: None,
+            jobs: None,
             debug_pandoc_cmd: false,
         }
     }
@@ -107,6 +216,9 @@ pub struct DwwbInputs {
     style: PathBuf,
     /// The path to the pandoc template for the article
     article_template: PathBuf,
+    /// The path to the template of the markdown source of the generated category pages
+    #[serde(default = "default_category_template")]
+    category_template: PathBuf,
     /// The glob for all the markdown articles
     articles: DirGlob,
     /// The globs for all other files to be included in the output
@@ -130,6 +242,13 @@ impl DwwbInputs {
         &self.article_template
     }
 
+    /// Returns the path of the template for the generated category pages
+    ///
+    /// The built-in template is used if the file doesn't exist.
+    pub fn category_template(&self) -> &Path {
+        &self.category_template
+    }
+
     /// Returns the path of the articles input directory
     pub fn articles_dir(&self) -> &Path {
         &self.articles.base
@@ -172,10 +291,9 @@ impl DwwbInputs {
     /// Makes sure that all of the input directories exist by creating them otherwise
     ///
     /// Make sure to call inside the project directory.
-    pub fn ensure_dirs_exists(&self) -> Result<(), String> {
+    pub fn ensure_dirs_exists(&self) -> Result<()> {
         let f = |p: &Path| {
-            std::fs::create_dir_all(p)
-                .map_err(|e| format!("Input directory '{}' couldn't be created: {e}", p.display()))
+            std::fs::create_dir_all(p).map_err(DwwbError::io("creating the input directory", p))
         };
 
         if let Some(path) = self.index.parent() {
@@ -194,12 +312,17 @@ impl DwwbInputs {
     }
 }
 
+fn default_category_template() -> PathBuf {
+    "templates/category.md".into()
+}
+
 impl Default for DwwbInputs {
     fn default() -> Self {
         Self {
             index: "index.md".into(),
             style: "style.css".into(),
             article_template: "templates/dwwb-article.html".into(),
+            category_template: default_category_template(),
             articles: DirGlob::new("articles", ["**/*.{md,markdown}"]),
             others: BTreeMap::from([("scripts".to_string(), DirGlob::new("scripts", ["**/*.js"]))]),
         }
@@ -231,6 +354,11 @@ impl DwwbOutputs {
         &self.root
     }
 
+    /// Changes the root output directory path
+    pub fn set_root<P: Into<PathBuf>>(&mut self, root: P) {
+        self.root = root.into();
+    }
+
     /// Returns the path to the output stylesheet relative to the root
     pub fn style(&self) -> &Path {
         &self.style
@@ -261,14 +389,9 @@ impl DwwbOutputs {
     /// Makes sure that all of the output directories exist by creating them otherwise
     ///
     /// Make sure to call inside the project directory.
-    pub fn ensure_dirs_exists(&self) -> Result<(), String> {
+    pub fn ensure_dirs_exists(&self) -> Result<()> {
         let f = |p: &Path| {
-            std::fs::create_dir_all(p).map_err(|e| {
-                format!(
-                    "Output directory '{}' couldn't be created: {e}",
-                    p.display()
-                )
-            })
+            std::fs::create_dir_all(p).map_err(DwwbError::io("creating the output directory", p))
         };
 
         f(&self.root)?;
@@ -295,6 +418,54 @@ impl Default for DwwbOutputs {
     }
 }
 
+/// The ways to sort the sibling articles in the sidebar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidebarSort {
+    /// By the titles, ignoring the case
+    Title,
+    /// By the ids, which are the file and directory names
+    #[default]
+    Path,
+    /// By the `weight` or `order` metadata values, and then by the titles
+    ///
+    /// The articles without a weight have the weight 0.
+    Weight,
+}
+
+/// The settings of the feed of the recently changed articles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FeedConfig {
+    /// The title of the feed, which defaults to the name of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The author of the feed, which defaults to the name of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// The number of the most recently changed articles in the feed
+    #[serde(default = "default_feed_entries")]
+    pub entries: usize,
+    /// Whether to write an RSS 2.0 feed in addition to the Atom feed
+    #[serde(default)]
+    pub rss: bool,
+}
+
+fn default_feed_entries() -> usize {
+    20
+}
+
+/// The backends for converting the markdown articles into html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// Pandoc, which needs to be installed separately
+    #[default]
+    Pandoc,
+    /// The built-in markdown renderer, which supports only a subset of the features of pandoc
+    Native,
+}
+
 /// Corresponds to the tex rendering engines available for HTML in Pandoc.
 ///
 /// <https://pandoc.org/MANUAL.html#math-rendering-in-html>
@@ -347,4 +518,15 @@ impl DirGlob {
         GlobWalkerBuilder::from_patterns(&self.base, &self.patterns)
             .file_type(globwalk::FileType::FILE)
     }
+
+    /// Converts this into a matcher that checks if a single path matches any of the patterns
+    ///
+    /// The matched paths should be relative to the base directory.
+    pub fn to_matcher(&self) -> Result<Override, ignore::Error> {
+        let mut builder = OverrideBuilder::new(&self.base);
+        for pattern in &self.patterns {
+            builder.add(pattern)?;
+        }
+        builder.build()
+    }
 }
@@ -0,0 +1,292 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// The result type used throughout the wiki builder
+pub type Result<T, E = DwwbError> = std::result::Result<T, E>;
+
+/// An error that stops the project from being created, checked or built
+#[derive(Debug, Error)]
+pub enum DwwbError {
+    /// The configuration file is missing, unreadable or invalid
+    #[error("Error in the configuration file '{}': {source}", path.display())]
+    Config { path: PathBuf, source: ConfigError },
+    /// Reading or writing a file or a directory failed
+    #[error("Error while {action} '{}': {source}", path.display())]
+    Io {
+        /// What was being done with the file, like "reading the file"
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Writing a YAML file, like the build cache or the pandoc defaults, failed
+    #[error("Error while writing the YAML file '{}': {source}", path.display())]
+    Yaml {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+    /// The YAML metadata block of an article is missing or invalid
+    #[error("Invalid metadata in the file '{}': {source}", path.display())]
+    Metadata {
+        path: PathBuf,
+        source: MetadataError,
+    },
+    /// Two articles have the same id, so they would be written into the same file
+    #[error("The files '{}' and '{}' have the same article id '{id}'", path.display(), other.display())]
+    IdConflict {
+        path: PathBuf,
+        other: PathBuf,
+        id: String,
+    },
+    /// A wiki link of an article doesn't match exactly one article
+    #[error("Invalid wiki link `[[{target}]]` in the file '{}': {reason}", path.display())]
+    WikiLink {
+        path: PathBuf,
+        target: String,
+        reason: String,
+    },
+    /// Pandoc failed to convert an article
+    #[error("Pandoc failed to convert the file '{}': {source}", path.display())]
+    Pandoc {
+        path: PathBuf,
+        source: pandoc::PandocError,
+    },
+    /// A glob pattern is invalid or its directory couldn't be traversed
+    #[error("Error with the glob of the directory '{}': {source}", path.display())]
+    Glob {
+        /// The base directory of the glob
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A relative link of a generated html file doesn't point to an existing file or element
+    #[error("Broken link `{url}` in the file '{}': {reason}", path.display())]
+    BrokenLink {
+        path: PathBuf,
+        url: String,
+        reason: String,
+    },
+    /// An article template couldn't be parsed or rendered
+    #[error("Template error in the file '{}': {message}", path.display())]
+    Template { path: PathBuf, message: String },
+    /// The file watcher of the `watch` command failed
+    #[error("File watcher error: {source}")]
+    Watch { source: notify::Error },
+    /// The local HTTP server couldn't be started
+    #[error("Error while starting the server at '{address}': {source}")]
+    Server { address: String, source: io::Error },
+    /// Some of the articles failed to build in the `keep_going` mode
+    #[error("{} article(s) failed to build:{}", .0.len(), list_errors(.0))]
+    ArticlesFailed(Vec<DwwbError>),
+    /// The link check of the build found broken links
+    #[error("{} broken link(s) found:{}", .0.len(), list_errors(.0))]
+    LinksBroken(Vec<DwwbError>),
+}
+
+impl DwwbError {
+    /// Returns a function that wraps an IO error with the given action and path
+    ///
+    /// Meant for `map_err`.
+    pub fn io<P: AsRef<Path>>(action: &'static str, path: P) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| Self::Io {
+            action,
+            path,
+            source,
+        }
+    }
+
+    /// Returns a function that wraps a glob error with the base directory of the glob
+    ///
+    /// Meant for `map_err`.
+    pub fn glob<P: AsRef<Path>, E: std::error::Error + Send + Sync + 'static>(
+        path: P,
+    ) -> impl FnOnce(E) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| Self::Glob {
+            path,
+            source: Box::new(source),
+        }
+    }
+
+    /// Returns the path of the file or the directory that the error is about, if there is one
+    pub fn path(&self) -> Option<&Path> {
+        use DwwbError::*;
+        match self {
+            Config { path, .. }
+            | Io { path, .. }
+            | Yaml { path, .. }
+            | Metadata { path, .. }
+            | IdConflict { path, .. }
+            | WikiLink { path, .. }
+            | BrokenLink { path, .. }
+            | Pandoc { path, .. }
+            | Glob { path, .. }
+            | Template { path, .. } => Some(path),
+            Watch { .. } | Server { .. } | ArticlesFailed(_) | LinksBroken(_) => None,
+        }
+    }
+}
+
+/// The ways the configuration file can be invalid
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("the file does not exist")]
+    NotFound,
+    #[error("{0}")]
+    Read(#[from] io::Error),
+    #[error("{0}")]
+    Parse(#[from] serde_yaml::Error),
+    /// The file was parsed, but one of the values isn't allowed
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// The ways the YAML metadata block of an article can be invalid
+#[derive(Debug, Error)]
+pub enum MetadataError {
+    #[error("expected a YAML metadata block at the start of the file")]
+    MissingBlock,
+    #[error("{0}")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("no `title` in the YAML metadata block")]
+    MissingTitle,
+    /// A known key has a value of the wrong type
+    #[error("expected {expected} as the `{key}`, instead found {found}")]
+    WrongType {
+        key: &'static str,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+fn list_errors(errors: &[DwwbError]) -> String {
+    errors.iter().map(|e| format!("\n{e}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::build::ArticleSidebarData;
+    use crate::config::{DwwbConfig, CFG_FILENAME};
+
+    #[test]
+    fn config_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CFG_FILENAME);
+        let load = || DwwbConfig::from_file(Some(dir.path().to_path_buf())).unwrap_err();
+
+        let e = load();
+        assert!(matches!(
+            &e,
+            DwwbError::Config {
+                source: ConfigError::NotFound,
+                ..
+            }
+        ));
+        assert_eq!(e.path(), Some(path.as_path()));
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "Error in the configuration file '{}': the file does not exist",
+                path.display()
+            )
+        );
+
+        fs::write(&path, "name: [").unwrap();
+        assert!(matches!(
+            load(),
+            DwwbError::Config {
+                source: ConfigError::Parse(_),
+                ..
+            }
+        ));
+
+        let cfg = DwwbConfig {
+            jobs: Some(0),
+            name: "wiki".to_string(),
+            ..Default::default()
+        };
+        fs::write(&path, serde_yaml::to_string(&cfg).unwrap()).unwrap();
+        let e = load();
+        assert!(matches!(
+            &e,
+            DwwbError::Config {
+                source: ConfigError::Invalid(_),
+                ..
+            }
+        ));
+        assert!(e.to_string().ends_with(": `jobs` must be at least 1"));
+    }
+
+    #[test]
+    fn metadata_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alice.md");
+        let read = |source: &str| {
+            fs::write(&path, source).unwrap();
+            ArticleSidebarData::from_article_meta(&DwwbConfig::default(), &path).unwrap_err()
+        };
+
+        let e = read("No metadata\n");
+        assert!(matches!(
+            &e,
+            DwwbError::Metadata {
+                source: MetadataError::MissingBlock,
+                ..
+            }
+        ));
+        assert_eq!(e.path(), Some(path.as_path()));
+
+        let e = read("---\nkeywords: []\n---\n");
+        assert!(matches!(
+            &e,
+            DwwbError::Metadata {
+                source: MetadataError::MissingTitle,
+                ..
+            }
+        ));
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "Invalid metadata in the file '{}': no `title` in the YAML metadata block",
+                path.display()
+            )
+        );
+
+        let e = read("---\ntitle: Alice\nweight: heavy\n---\n");
+        assert!(matches!(
+            &e,
+            DwwbError::Metadata {
+                source: MetadataError::WrongType { key: "weight", .. },
+                ..
+            }
+        ));
+        assert!(e
+            .to_string()
+            .ends_with(": expected an integer as the `weight`, instead found a string"));
+    }
+
+    #[test]
+    fn id_conflict_errors() {
+        let e = DwwbError::IdConflict {
+            path: "articles/alice.markdown".into(),
+            other: "articles/alice.md".into(),
+            id: "alice".to_string(),
+        };
+        assert_eq!(e.path(), Some(Path::new("articles/alice.markdown")));
+        assert_eq!(
+            e.to_string(),
+            "The files 'articles/alice.markdown' and 'articles/alice.md' have the same article id 'alice'"
+        );
+
+        let failed = DwwbError::ArticlesFailed(vec![e]);
+        assert_eq!(failed.path(), None);
+        assert_eq!(
+            failed.to_string(),
+            "1 article(s) failed to build:\nThe files 'articles/alice.markdown' and 'articles/alice.md' have the same article id 'alice'"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+// Injected only into the development builds of `dwwb build --live-reload`
+// Reloads the page every time the wiki has been rebuilt
+(function () {
+    var events = new EventSource(new URL("live-reload", document.currentScript.src));
+    events.addEventListener("reload", function () {
+        window.location.reload();
+    });
+})();
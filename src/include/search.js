@@ -0,0 +1,91 @@
+// The search of the wiki, written by `dwwb new`, or by the build if the project has no search script
+// Shows the articles matching the `q` parameter on the generated search page
+(function () {
+    "use strict";
+
+    var form = document.getElementById("search");
+    var results = document.getElementById("search-results");
+    if (!form || !results) {
+        return;
+    }
+
+    var query = (new URLSearchParams(window.location.search).get("q") || "").trim();
+    form.elements.q.value = query;
+    var terms = query.toLowerCase().split(/\s+/).filter(Boolean);
+    if (terms.length === 0) {
+        return;
+    }
+
+    // the index is loaded as a script, as the pages may be opened from the local file system
+    var script = document.createElement("script");
+    script.src = form.dataset.index;
+    script.onload = function () {
+        show(search(window.dwwbSearchIndex || []));
+    };
+    script.onerror = function () {
+        results.textContent = "The search index could not be loaded.";
+    };
+    document.head.appendChild(script);
+
+    function count(text, term) {
+        var n = 0;
+        for (var i = text.indexOf(term); i !== -1; i = text.indexOf(term, i + term.length)) {
+            n++;
+        }
+        return n;
+    }
+
+    // every term must be found, and the matches in the titles and the keywords count the most
+    function search(entries) {
+        var matches = [];
+        entries.forEach(function (entry) {
+            var title = entry.title.toLowerCase();
+            var keywords = entry.keywords.join(" ").toLowerCase();
+            var body = entry.body.toLowerCase();
+            var score = 0;
+            for (var i = 0; i < terms.length; i++) {
+                var hits = count(title, terms[i]) * 10 + count(keywords, terms[i]) * 5 + count(body, terms[i]);
+                if (hits === 0) {
+                    return;
+                }
+                score += hits;
+            }
+            matches.push({ entry: entry, score: score });
+        });
+        matches.sort(function (a, b) {
+            return b.score - a.score;
+        });
+        return matches;
+    }
+
+    function snippet(body) {
+        var start = body.toLowerCase().indexOf(terms[0]);
+        if (start === -1) {
+            return body.slice(0, 200);
+        }
+        start = Math.max(0, start - 80);
+        return (start > 0 ? "…" : "") + body.slice(start, start + 200) + "…";
+    }
+
+    function show(matches) {
+        results.textContent = "";
+        var summary = document.createElement("p");
+        summary.textContent = matches.length + " result(s) for \"" + query + "\"";
+        results.appendChild(summary);
+
+        var list = document.createElement("ul");
+        matches.forEach(function (match) {
+            var item = document.createElement("li");
+            var link = document.createElement("a");
+            // the urls in the index are relative to the output root, like the index itself
+            link.href = new URL(match.entry["link-url"], script.src).href;
+            link.textContent = match.entry.title;
+            var text = document.createElement("p");
+            text.textContent = snippet(match.entry.body);
+            item.appendChild(link);
+            item.appendChild(text);
+            list.appendChild(item);
+        });
+        results.appendChild(list);
+    }
+})();
@@ -0,0 +1,24 @@
+//! Dreamweaver's Wiki Builder
+//!
+//! Builds a html wiki from markdown articles with pandoc or the built-in renderer.
+//! The `dwwb` binary is a thin command line interface on top of this library,
+//! so the same builds can be embedded into other tools.
+
+mod build;
+mod check;
+pub mod config;
+pub mod error;
+mod new;
+mod report;
+mod serve;
+mod util;
+mod watch;
+
+pub use build::{build_project, clean_project, ArticleSidebarData, BuildOptions};
+pub use check::check_project;
+pub use config::DwwbConfig;
+pub use error::{DwwbError, Result};
+pub use new::{create_article, create_new};
+pub use report::{QuietReporter, Reporter};
+pub use serve::serve_dir;
+pub use watch::{live_reload_project, watch_project};
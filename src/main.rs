@@ -1,19 +1,13 @@
-mod build;
-mod config;
-mod new;
-mod util;
-
-use std::io::Write;
+use std::path::PathBuf;
 use std::process::ExitCode;
-use std::{fs::File, path::PathBuf};
 
+use clap::builder::RangedU64ValueParser;
 use clap::{Parser, Subcommand};
 
-use build::build_project;
-use config::DwwbConfig;
-use new::create_new;
-
-use crate::util::title_case;
+use dwwb::{
+    build_project, check_project, clean_project, create_article, create_new, live_reload_project,
+    serve_dir, watch_project, BuildOptions, DwwbConfig, DwwbError, Reporter,
+};
 
 /// Builds a html wiki from the given markdown content with pandoc.
 ///
@@ -31,11 +25,46 @@ pub struct Args {
     subcommand: DwwbCommand,
 }
 
-impl Args {
+impl Reporter for Args {
     /// Prints the given message if the quiet flag is not set
-    pub fn msg<S: ToString>(&self, msg: S) {
+    fn message(&self, msg: &str) {
         if !self.quiet {
-            println!("{}", msg.to_string())
+            println!("{msg}")
+        }
+    }
+}
+
+/// The options of the commands that build the project
+#[derive(Debug, Clone, clap::Args)]
+struct BuildArgs {
+    /// Converts every article, even the ones that haven't changed since the previous build
+    #[arg(short, long)]
+    force: bool,
+    /// The number of articles to convert in parallel, overrides the `jobs` value of `dwwb.yaml`
+    ///
+    /// Defaults to the number of available CPU cores.
+    #[arg(short, long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    jobs: Option<usize>,
+    /// Converts every article it can instead of stopping at the first error
+    ///
+    /// The failed articles are listed at the end, and the command still exits with an error.
+    #[arg(short, long)]
+    keep_going: bool,
+    /// Checks that the relative links of the built site point to existing files and element ids
+    ///
+    /// The broken links are listed at the end, and the command exits with an error.
+    #[arg(long)]
+    check_links: bool,
+}
+
+impl BuildArgs {
+    fn to_options(&self) -> BuildOptions {
+        BuildOptions {
+            force: self.force,
+            jobs: self.jobs,
+            keep_going: self.keep_going,
+            check_links: self.check_links,
+            ..Default::default()
         }
     }
 }
@@ -51,7 +80,24 @@ enum DwwbCommand {
     },
     /// Builds the wiki project into a html site
     #[command()]
-    Build,
+    Build {
+        #[command(flatten)]
+        opts: BuildArgs,
+        /// Keeps running, rebuilding the site every time the inputs change, and reloading the open pages
+        ///
+        /// The development build is written into a temporary directory and served on a local server,
+        /// so the live reload script never ends up in the actual output directory.
+        #[arg(long)]
+        live_reload: bool,
+        /// The localhost port for the live reload server, 8000 by default
+        #[arg(short, long, requires = "live_reload")]
+        port: Option<u16>,
+    },
+    /// Checks the wiki project for errors without building it
+    ///
+    /// Reports all of the problems found, instead of stopping at the first one.
+    #[command()]
+    Check,
     /// Cleans the built html site
     #[command()]
     Clean,
@@ -66,18 +112,25 @@ enum DwwbCommand {
         #[arg()]
         path: PathBuf,
     },
+    /// Builds the wiki project and rebuilds it every time the input files change
+    #[command()]
+    Watch {
+        #[command(flatten)]
+        opts: BuildArgs,
+    },
+    /// Builds the wiki project and serves it over a local HTTP server
+    #[command()]
+    Serve {
+        /// The localhost port to serve the built site on
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+    },
 }
 
-#[macro_export]
-macro_rules! uw {
-    ($e:expr, $msg:expr) => {
-        match $e {
-            Ok(x) => x,
-            Err(e) => {
-                return Err(format!("Error while {}: {}", $msg, e));
-            }
-        }
-    };
+/// Prints the error and returns the failure exit code
+fn report_error(e: DwwbError) -> ExitCode {
+    eprintln!("{e}");
+    ExitCode::FAILURE
 }
 
 fn main() -> ExitCode {
@@ -87,83 +140,88 @@ fn main() -> ExitCode {
     match &args.subcommand {
         New { path } => match create_new(path) {
             Ok(()) => {
-                args.msg(format!("New project created at {}", path.display()));
+                args.message(&format!("New project created at {}", path.display()));
                 ExitCode::SUCCESS
             }
-            Err(e) => {
-                eprintln!("{e}");
-                ExitCode::FAILURE
-            }
+            Err(e) => report_error(e),
         },
-        Build => match DwwbConfig::from_file(None) {
+        Build {
+            opts,
+            live_reload: true,
+            port,
+        } => {
+            if let Err(e) = live_reload_project(&args, &opts.to_options(), port.unwrap_or(8000)) {
+                report_error(e)
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Build { opts, .. } => match DwwbConfig::from_file(None) {
             Ok(cfg) => {
-                if let Err(e) = build_project(cfg, args) {
-                    eprintln!("Build error: {e}");
-                    ExitCode::FAILURE
+                if let Err(e) = build_project(cfg, &args, &opts.to_options()) {
+                    report_error(e)
                 } else {
                     ExitCode::SUCCESS
                 }
             }
-            Err(e) => {
-                eprintln!("Configuration error: {e}");
-                ExitCode::FAILURE
-            }
+            Err(e) => report_error(e),
         },
-        Clean => match DwwbConfig::from_file(None) {
+        Check => match DwwbConfig::from_file(None) {
             Ok(cfg) => {
-                args.msg(format!(
-                    "Removing the output directory '{}'...",
-                    cfg.outputs.root().display()
-                ));
-                if let Err(e) = std::fs::remove_dir_all(cfg.outputs.root()) {
-                    eprintln!("Error while removing the output directory: {e}");
-                    ExitCode::FAILURE
-                } else {
-                    args.msg("All done");
+                args.message("Checking the project...");
+                let problems = check_project(&cfg);
+                for problem in &problems {
+                    eprintln!("{problem}");
+                }
+
+                if problems.is_empty() {
+                    args.message("No problems found");
                     ExitCode::SUCCESS
+                } else {
+                    eprintln!("---\n{} problem(s) found", problems.len());
+                    ExitCode::FAILURE
                 }
             }
-            Err(e) => {
-                eprintln!("Configuration error: {e}");
-                ExitCode::FAILURE
-            }
+            Err(e) => report_error(e),
+        },
+        Clean => match DwwbConfig::from_file(None) {
+            Ok(cfg) => match clean_project(&cfg, &args) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => report_error(e),
+            },
+            Err(e) => report_error(e),
         },
         Add { path } => match DwwbConfig::from_file(None) {
-            Ok(cfg) => {
-                let mut path = cfg.inputs.articles_dir().join(path);
-                if path.extension().is_none() {
-                    path.set_extension("md");
+            Ok(cfg) => match create_article(&cfg, path) {
+                Ok(path) => {
+                    args.message(&format!("File '{}' created", path.display()));
+                    ExitCode::SUCCESS
                 }
-
-                if path.exists() {
-                    eprintln!("File '{}' already exists", path.display());
-                    return ExitCode::FAILURE;
+                Err(e) => report_error(e),
+            },
+            Err(e) => report_error(e),
+        },
+        Watch { opts } => {
+            if let Err(e) = watch_project(&args, &opts.to_options()) {
+                report_error(e)
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Serve { port } => match DwwbConfig::from_file(None) {
+            Ok(cfg) => {
+                let root = cfg.outputs.root().to_path_buf();
+                if let Err(e) = build_project(cfg, &args, &BuildOptions::default()) {
+                    return report_error(e);
                 }
 
-                match File::create(&path) {
-                    Ok(mut article) => {
-                        let title = title_case(&path.file_stem().unwrap().to_string_lossy());
-                        if let Err(e) = write!(
-                            &mut article,
-                            "---\n# Pandoc metadata\ntitle: {title}\nkeywords: []\n---\n\nText goes here.\n"
-                        ) {
-                            eprintln!("Error while writing file '{}': {e}", path.display());
-                            return ExitCode::FAILURE;
-                        }
-
-                        args.msg(format!("File '{}' created", path.display()));
-                        ExitCode::SUCCESS
-                    }
-                    Err(e) => {
-                        eprintln!("Error while creating file '{}': {e}", path.display());
-                        ExitCode::FAILURE
-                    }
+                if let Err(e) = serve_dir(&root, *port, &args) {
+                    report_error(e)
+                } else {
+                    ExitCode::SUCCESS
                 }
             }
-            Err(e) => {
-                eprintln!("Configuration error: {e}");
-                ExitCode::FAILURE
-            }
+            Err(e) => report_error(e),
         },
     }
 }
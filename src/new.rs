@@ -1,27 +1,34 @@
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::{fs, fs::File, path::Path};
 
 use crate::config::{DwwbConfig, CFG_FILENAME};
-use crate::uw;
+use crate::error::{DwwbError, Result};
+use crate::util::title_case;
 
 /// Creates a new wiki project
-pub fn create_new(path: &Path) -> Result<(), String> {
+pub fn create_new(path: &Path) -> Result<()> {
+    let creating_error = DwwbError::io("creating the project directory", path);
     if path.exists() {
-        return Err(format!("The directory '{}' exists already", path.display()));
+        return Err(creating_error(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the directory exists already",
+        )));
     }
 
-    let name = path
-        .file_name()
-        .ok_or_else(|| "No name given".to_string())?
-        .to_string_lossy()
-        .to_string();
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => {
+            return Err(creating_error(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no name given",
+            )))
+        }
+    };
 
-    uw!(fs::create_dir_all(path), "creating directories");
-    uw!(
-        std::env::set_current_dir(path),
-        "changing the working directory"
-    );
+    fs::create_dir_all(path).map_err(creating_error)?;
+    std::env::set_current_dir(path)
+        .map_err(DwwbError::io("changing the working directory to", path))?;
 
     let cfg = &DwwbConfig {
         name: name.clone(),
@@ -29,65 +36,87 @@ pub fn create_new(path: &Path) -> Result<(), String> {
     };
     cfg.inputs.ensure_dirs_exists()?;
 
-    let file = |filename: &Path, description| {
+    // creates the file and writes the contents into it
+    let file = |filename: &Path, contents: &[u8]| {
         File::create(filename)
-            .map_err(|e| format!("Error while creating the {description} file: {e}"))
+            .and_then(|mut file| file.write_all(contents))
+            .map_err(DwwbError::io("writing the file", filename))
     };
 
-    let cfg_file = file(&PathBuf::from(CFG_FILENAME), "configuration")?;
+    let cfg_path = PathBuf::from(CFG_FILENAME);
+    let cfg_file =
+        File::create(&cfg_path).map_err(DwwbError::io("creating the file", &cfg_path))?;
+    serde_yaml::to_writer(cfg_file, cfg).map_err(|source| DwwbError::Yaml {
+        path: cfg_path,
+        source,
+    })?;
 
-    let mut css = file(cfg.inputs.style(), "stylesheet")?;
-    let _script = file(&cfg.inputs.scripts_dir().join("main.js"), "script")?;
+    file(cfg.inputs.style(), include_bytes!("include/style.css"))?;
+    file(&cfg.inputs.scripts_dir().join("main.js"), b"")?;
+    file(
+        &cfg.inputs.scripts_dir().join("search.js"),
+        include_bytes!("include/search.js"),
+    )?;
 
-    let mut article_template = file(&cfg.inputs.article_template(), "article template")?;
-    let mut sidebar_template = file(
-        &cfg.inputs
-            .article_template()
-            .parent()
-            .unwrap_or(&PathBuf::default())
-            .join("sidebar.html"),
-        "sidebar template",
+    file(
+        cfg.inputs.article_template(),
+        include_bytes!("include/templates/dwwb-article.html"),
+    )?;
+    // the partials of the template must be in the same directory
+    let template_dir = cfg
+        .inputs
+        .article_template()
+        .parent()
+        .unwrap_or(Path::new(""));
+    file(
+        &template_dir.join("sidebar.html"),
+        include_bytes!("include/templates/sidebar.html"),
+    )?;
+    file(
+        &template_dir.join("sidebar-tree.html"),
+        include_bytes!("include/templates/sidebar-tree.html"),
+    )?;
+    file(
+        cfg.inputs.category_template(),
+        include_bytes!("include/templates/category.md"),
     )?;
 
-    let mut index = file(&PathBuf::from("index.md"), "index")?;
-    let mut article = file(
+    file(
+        &PathBuf::from("index.md"),
+        format!("---\n# Pandoc metadata\ntitle: {name}\nkeywords:\n- site\n---\n\nHello world!\n")
+            .as_bytes(),
+    )?;
+    file(
         &cfg.inputs.articles_dir().join("example.md"),
-        "example article",
+        b"---\n# Pandoc metadata\ntitle: Example\nkeywords: []\n---\n\nExample article.\n",
     )?;
 
-    uw!(
-        serde_yaml::to_writer(cfg_file, cfg),
-        "writing the configuration file"
-    );
-
-    uw!(
-        css.write_all(include_bytes!("include/style.css")),
-        "writing the stylesheet file"
-    );
-    uw!(
-        article_template.write_all(include_bytes!("include/templates/dwwb-article.html")),
-        "writing the article template file"
-    );
-    uw!(
-        sidebar_template.write_all(include_bytes!("include/templates/sidebar.html")),
-        "writing the sidebar template file"
-    );
+    Ok(())
+}
 
-    uw!(
-        write!(
-            index,
-            "---\n# Pandoc metadata\ntitle: {name}\nkeywords:\n- site\n---\n\nHello world!\n",
-        ),
-        "writing the index file"
-    );
+/// Creates a new article with the given path in the articles input directory
+///
+/// The `md` extension is added if the path has none, and the title is generated from the file name.
+///
+/// Returns the path of the created file.
+pub fn create_article(cfg: &DwwbConfig, path: &Path) -> Result<PathBuf> {
+    let mut path = cfg.inputs.articles_dir().join(path);
+    if path.extension().is_none() {
+        path.set_extension("md");
+    }
 
-    uw!(
-        write!(
-            article,
-            "---\n# Pandoc metadata\ntitle: Example\nkeywords: []\n---\n\nExample article.\n",
-        ),
-        "writing the example article"
-    );
+    // fails if the file exists already
+    let mut article = File::options()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(DwwbError::io("creating the file", &path))?;
+    let title = title_case(&path.file_stem().unwrap_or_default().to_string_lossy());
+    write!(
+        article,
+        "---\n# Pandoc metadata\ntitle: {title}\nkeywords: []\n---\n\nText goes here.\n"
+    )
+    .map_err(DwwbError::io("writing the file", &path))?;
 
-    Ok(())
+    Ok(path)
 }
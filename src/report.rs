@@ -0,0 +1,27 @@
+use crate::error::DwwbError;
+
+/// Receives the progress of the builds and the other commands
+///
+/// Lets the tools that embed the wiki builder show the progress however they want.
+/// Shared between the worker threads of the build, so it must be thread safe.
+pub trait Reporter: Send + Sync {
+    /// Reports a progress message, like the name of a converted article
+    fn message(&self, msg: &str);
+
+    /// Reports an error that doesn't stop the command, like a failure to delete an old output file
+    ///
+    /// Prints the error to the stderr by default.
+    fn warning(&self, error: &DwwbError) {
+        eprintln!("{error}");
+    }
+}
+
+/// A reporter that ignores the progress messages
+///
+/// The warnings are still printed to the stderr.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuietReporter;
+
+impl Reporter for QuietReporter {
+    fn message(&self, _msg: &str) {}
+}
@@ -0,0 +1,381 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::error::{DwwbError, Result};
+use crate::report::Reporter;
+use crate::util::escape_html;
+
+/// The URL path of the live reload script, relative to the root
+pub const LIVE_RELOAD_SCRIPT: &str = "__dwwb/live-reload.js";
+/// The URL path of the server-sent event stream that tells the browser when to reload
+const LIVE_RELOAD_EVENTS: &str = "__dwwb/live-reload";
+
+/// A local HTTP server for previewing the built site
+#[derive(Debug)]
+pub struct PreviewServer {
+    listener: TcpListener,
+    root: PathBuf,
+    live_reload: Option<LiveReload>,
+}
+
+impl PreviewServer {
+    /// Creates a new server for the given directory and binds it to the given localhost port
+    pub fn bind(root: &Path, port: u16) -> Result<Self> {
+        let listener =
+            TcpListener::bind(("127.0.0.1", port)).map_err(|source| DwwbError::Server {
+                address: format!("127.0.0.1:{port}"),
+                source,
+            })?;
+
+        Ok(Self {
+            listener,
+            root: root.to_path_buf(),
+            live_reload: None,
+        })
+    }
+
+    /// Enables the live reload script and its event stream endpoint
+    pub fn with_live_reload(mut self, live_reload: LiveReload) -> Self {
+        self.live_reload = Some(live_reload);
+        self
+    }
+
+    /// Returns the URL of the root of the server
+    pub fn url(&self) -> String {
+        match self.listener.local_addr() {
+            Ok(addr) => format!("http://{addr}/"),
+            Err(_) => "http://127.0.0.1/".to_string(),
+        }
+    }
+
+    /// Serves the files until the process is stopped
+    pub fn run<R: Reporter + Clone + 'static>(self, reporter: &R) {
+        let server_error = |source| DwwbError::Server {
+            address: self.url(),
+            source,
+        };
+
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    reporter.warning(&server_error(e));
+                    continue;
+                }
+            };
+
+            let root = self.root.clone();
+            let live_reload = self.live_reload.clone();
+            let reporter = reporter.clone();
+            let address = self.url();
+            thread::spawn(move || {
+                if let Err(source) =
+                    handle_connection(&root, live_reload.as_ref(), stream, &reporter)
+                {
+                    reporter.warning(&DwwbError::Server { address, source });
+                }
+            });
+        }
+    }
+}
+
+/// The browser tabs connected to the live reload event stream
+#[derive(Debug, Clone, Default)]
+pub struct LiveReload {
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl LiveReload {
+    /// Tells all of the connected browser tabs to reload the page
+    pub fn reload(&self) {
+        let mut clients = self.clients.lock().unwrap();
+        // the tabs that have been closed are dropped here
+        clients.retain_mut(|stream| {
+            stream
+                .write_all(b"event: reload\ndata:\n\n")
+                .and_then(|_| stream.flush())
+                .is_ok()
+        });
+    }
+
+    /// Starts the event stream for a new client
+    fn connect(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+        )?;
+        stream.flush()?;
+        self.clients.lock().unwrap().push(stream);
+        Ok(())
+    }
+}
+
+/// Serves the given output directory over a local HTTP server until the process is stopped
+pub fn serve_dir<R: Reporter + Clone + 'static>(
+    root: &Path,
+    port: u16,
+    reporter: &R,
+) -> Result<()> {
+    let server = PreviewServer::bind(root, port)?;
+    reporter.message(&format!(
+        "---\nServing '{}' at {}\nPress Ctrl+C to stop",
+        root.display(),
+        server.url()
+    ));
+    server.run(reporter);
+    Ok(())
+}
+
+/// Reads a single request from the stream and writes the response
+fn handle_connection(
+    root: &Path,
+    live_reload: Option<&LiveReload>,
+    mut stream: TcpStream,
+    reporter: &dyn Reporter,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // skip the headers, they are not needed for anything
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return respond(&mut stream, 400, "text/plain", b"Bad request", false),
+    };
+    let head_only = method == "HEAD";
+    if method != "GET" && !head_only {
+        return respond(&mut stream, 405, "text/plain", b"Method not allowed", false);
+    }
+
+    // drop the query string and the fragment
+    let url_path = target.split(['?', '#']).next().unwrap_or_default();
+
+    if let Some(live_reload) = live_reload {
+        match url_path.trim_start_matches('/') {
+            LIVE_RELOAD_SCRIPT => {
+                let script = include_bytes!("include/live-reload.js");
+                return respond(
+                    &mut stream,
+                    200,
+                    content_type(Path::new(LIVE_RELOAD_SCRIPT)),
+                    script,
+                    head_only,
+                );
+            }
+            LIVE_RELOAD_EVENTS if !head_only => return live_reload.connect(stream),
+            _ => {}
+        }
+    }
+
+    match resolve_url_path(root, url_path) {
+        UrlTarget::File(path) => respond_file(&mut stream, &path, head_only),
+        UrlTarget::Redirect(location) => {
+            let response = format!(
+                "HTTP/1.1 301 Moved Permanently\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+            stream.write_all(response.as_bytes())
+        }
+        UrlTarget::NotFound => respond_not_found(&mut stream, url_path, head_only, reporter),
+    }
+}
+
+/// What a request for a url path is answered with
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UrlTarget {
+    File(PathBuf),
+    /// A redirect to the given location
+    Redirect(String),
+    NotFound,
+}
+
+/// Finds the file that the given url path points to
+///
+/// The directories are served with their `index.html` files.
+fn resolve_url_path(root: &Path, url_path: &str) -> UrlTarget {
+    let file_path = match url_to_file_path(root, url_path) {
+        Some(path) => path,
+        None => return UrlTarget::NotFound,
+    };
+
+    if file_path.is_dir() {
+        if !url_path.ends_with('/') {
+            // redirect so that the relative links inside the index page work correctly
+            return UrlTarget::Redirect(format!("{url_path}/"));
+        }
+
+        let index = file_path.join("index.html");
+        if index.is_file() {
+            return UrlTarget::File(index);
+        }
+    } else if file_path.is_file() {
+        return UrlTarget::File(file_path);
+    }
+
+    UrlTarget::NotFound
+}
+
+/// Converts the given url path to a path inside the root directory
+///
+/// Returns `None` if the path would escape the root directory.
+fn url_to_file_path(root: &Path, url_path: &str) -> Option<PathBuf> {
+    let decoded = url_escape::decode(url_path);
+    let mut path = root.to_path_buf();
+    for comp in Path::new(decoded.trim_start_matches('/')).components() {
+        match comp {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+fn respond_file(stream: &mut TcpStream, path: &Path, head_only: bool) -> std::io::Result<()> {
+    match fs::read(path) {
+        Ok(contents) => respond(stream, 200, content_type(path), &contents, head_only),
+        Err(_) => respond(
+            stream,
+            500,
+            "text/plain",
+            b"Error while reading the file",
+            head_only,
+        ),
+    }
+}
+
+fn respond_not_found(
+    stream: &mut TcpStream,
+    url_path: &str,
+    head_only: bool,
+    reporter: &dyn Reporter,
+) -> std::io::Result<()> {
+    reporter.message(&format!("Not found: {url_path}"));
+
+    let url_path = escape_html(url_path);
+    let page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<title>404 Not Found</title>\n</head>\n<body>\n<h1>404 Not Found</h1>\n<p>There is no article or file at <code>{url_path}</code>.</p>\n<p><a href=\"/\">Back to the index</a></p>\n</body>\n</html>\n"
+    );
+    respond(
+        stream,
+        404,
+        "text/html; charset=utf-8",
+        page.as_bytes(),
+        head_only,
+    )
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+    head_only: bool,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let header = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    if !head_only {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// Guesses the MIME type of the file from its extension
+fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_paths_stay_inside_the_root() {
+        let root = Path::new("html");
+        assert_eq!(
+            url_to_file_path(root, "/articles/./alice.html"),
+            Some(PathBuf::from("html/articles/alice.html"))
+        );
+        assert_eq!(url_to_file_path(root, "/../secret.txt"), None);
+        assert_eq!(url_to_file_path(root, "/articles/../../secret.txt"), None);
+        assert_eq!(
+            url_to_file_path(root, "/articles/%2E%2E/%2E%2E/secret.txt"),
+            None
+        );
+    }
+
+    #[test]
+    fn url_paths_are_decoded() {
+        assert_eq!(
+            url_to_file_path(Path::new("html"), "/articles/caf%C3%A9%20menu.html"),
+            Some(PathBuf::from("html/articles/café menu.html"))
+        );
+    }
+
+    #[test]
+    fn directories_are_served_with_their_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("articles/empty")).unwrap();
+        fs::write(root.join("articles/index.html"), "").unwrap();
+
+        assert_eq!(
+            resolve_url_path(root, "/articles/"),
+            UrlTarget::File(root.join("articles/index.html"))
+        );
+        assert_eq!(
+            resolve_url_path(root, "/articles"),
+            UrlTarget::Redirect("/articles/".to_string())
+        );
+        assert_eq!(
+            resolve_url_path(root, "/articles/empty/"),
+            UrlTarget::NotFound
+        );
+        assert_eq!(
+            resolve_url_path(root, "/articles/missing.html"),
+            UrlTarget::NotFound
+        );
+    }
+}
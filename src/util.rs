@@ -38,3 +38,31 @@ pub fn title_case<S: AsRef<str>>(input: &S) -> String {
         })
         .to_string()
 }
+
+/// Escapes the characters that could be read as markdown formatting
+pub fn escape_markdown(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_{}[]<>()#+-.!|~$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes the characters that have a special meaning in HTML and XML text
+pub fn escape_html<S: AsRef<str>>(input: S) -> String {
+    let mut output = String::with_capacity(input.as_ref().len());
+    for c in input.as_ref().chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
@@ -0,0 +1,317 @@
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use ignore::overrides::Override;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::build::{build_project, BuildOptions, CACHE_FILENAME};
+use crate::config::{DwwbConfig, CFG_FILENAME};
+use crate::error::{DwwbError, Result};
+use crate::report::Reporter;
+use crate::serve::{LiveReload, PreviewServer, LIVE_RELOAD_SCRIPT};
+
+/// How long to wait for further changes before starting the rebuild
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(300);
+
+/// The output directory of the live reload builds in the project directory
+///
+/// The directory is kept between the sessions, so that the build cache applies to it too.
+pub const LIVE_OUTPUT_DIR: &str = ".dwwb-live";
+
+/// Performs the `watch` command
+///
+/// Builds the project and then rebuilds it every time the inputs change, until the process is stopped.
+pub fn watch_project(reporter: &dyn Reporter, opts: &BuildOptions) -> Result<()> {
+    watch_and_rebuild(reporter, opts, None, || {})
+}
+
+/// Performs the `build --live-reload` command
+///
+/// Works like the `watch` command, but the site is built into the [`LIVE_OUTPUT_DIR`] directory,
+/// which is served with a preview server that reloads the open pages after every rebuild.
+/// This way the live reload script never ends up in the actual output directory.
+pub fn live_reload_project<R: Reporter + Clone + 'static>(
+    reporter: &R,
+    opts: &BuildOptions,
+    port: u16,
+) -> Result<()> {
+    let output_dir = Path::new(LIVE_OUTPUT_DIR);
+    let live_reload = LiveReload::default();
+    let server = PreviewServer::bind(output_dir, port)?.with_live_reload(live_reload.clone());
+    let url = server.url();
+    let server_reporter = reporter.clone();
+    thread::spawn(move || server.run(&server_reporter));
+
+    let mut opts = opts.clone();
+    opts.extra_script_files.push(LIVE_RELOAD_SCRIPT.to_string());
+
+    let mut first_build = true;
+    watch_and_rebuild(reporter, &opts, Some(output_dir), || {
+        if first_build {
+            reporter.message(&format!("---\nServing the development build at {url}"));
+            first_build = false;
+        }
+        live_reload.reload();
+    })
+}
+
+/// Rebuilds the project every time its inputs change
+///
+/// Overrides the output directory if one is given, and calls `after_build` after every successful build.
+fn watch_and_rebuild<F: FnMut()>(
+    reporter: &dyn Reporter,
+    opts: &BuildOptions,
+    output_root: Option<&Path>,
+    mut after_build: F,
+) -> Result<()> {
+    loop {
+        // the configuration is reloaded every time in case it was the file that was changed
+        let cfg = match DwwbConfig::from_file(None) {
+            Ok(mut cfg) => {
+                if let Some(root) = output_root {
+                    cfg.outputs.set_root(root);
+                }
+                Some(cfg)
+            }
+            Err(e) => {
+                reporter.warning(&e);
+                None
+            }
+        };
+        let inputs = WatchedInputs::new(cfg.as_ref())?;
+
+        // the watcher is set up before building so that no changes made during the build get lost
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|source| DwwbError::Watch { source })?;
+        for (path, mode) in inputs.watched_dirs() {
+            watcher
+                .watch(&path, mode)
+                .map_err(|source| DwwbError::Watch { source })?;
+        }
+
+        if let Some(cfg) = cfg {
+            match build_project(cfg, reporter, opts) {
+                Ok(()) => after_build(),
+                Err(e) => reporter.warning(&e),
+            }
+        }
+
+        reporter.message("---\nWatching for changes, press Ctrl+C to stop");
+        wait_for_changes(reporter, &rx, &inputs)?;
+        reporter.message("---\nChanges detected, rebuilding...");
+    }
+}
+
+/// Blocks until a relevant change happens and no further changes have happened for a moment
+///
+/// This way a burst of saves triggers only a single rebuild.
+fn wait_for_changes(
+    reporter: &dyn Reporter,
+    rx: &Receiver<notify::Result<Event>>,
+    inputs: &WatchedInputs,
+) -> Result<()> {
+    let disconnected = || DwwbError::Watch {
+        source: notify::Error::generic("the file watcher stopped unexpectedly"),
+    };
+
+    loop {
+        match rx.recv().map_err(|_| disconnected())? {
+            Ok(event) if inputs.is_relevant(&event) => break,
+            Ok(_) => {}
+            Err(source) => reporter.warning(&DwwbError::Watch { source }),
+        }
+    }
+
+    loop {
+        match rx.recv_timeout(DEBOUNCE_DELAY) {
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => return Err(disconnected()),
+        }
+    }
+}
+
+/// The set of input files that trigger a rebuild when changed
+struct WatchedInputs {
+    /// The current working directory, used for making the event paths relative
+    cwd: PathBuf,
+    /// Single files, like the index, the stylesheet and the configuration file
+    files: Vec<PathBuf>,
+    /// The directory of the article template
+    ///
+    /// All the files in it with the same extension as the template are watched,
+    /// as they can be used as partials by the template.
+    template_dir: Option<(PathBuf, OsString)>,
+    /// The base directories of the input globs and their matchers
+    globs: Vec<(PathBuf, Override)>,
+    /// The output directory, which is always ignored
+    output_root: Option<PathBuf>,
+}
+
+impl WatchedInputs {
+    fn new(cfg: Option<&DwwbConfig>) -> Result<Self> {
+        let cwd = std::env::current_dir()
+            .and_then(|p| p.canonicalize())
+            .map_err(DwwbError::io("reading the current working directory", "."))?;
+
+        let mut inputs = Self {
+            cwd,
+            files: vec![PathBuf::from(CFG_FILENAME)],
+            template_dir: None,
+            globs: Vec::new(),
+            output_root: None,
+        };
+
+        if let Some(cfg) = cfg {
+            let template = normalize(cfg.inputs.article_template());
+            inputs.files.push(normalize(cfg.inputs.index()));
+            inputs.files.push(normalize(cfg.inputs.style()));
+            inputs.files.push(template.clone());
+            inputs.files.push(normalize(cfg.inputs.category_template()));
+            inputs.template_dir = Some((
+                template.parent().unwrap_or(Path::new("")).to_path_buf(),
+                template.extension().unwrap_or_default().to_os_string(),
+            ));
+
+            let globs = std::iter::once(cfg.inputs.articles_glob())
+                .chain(cfg.inputs.non_articles_glob_iter().map(|(_, glob)| glob));
+            for glob in globs {
+                let matcher = glob.to_matcher().map_err(DwwbError::glob(&glob.base))?;
+                inputs.globs.push((normalize(&glob.base), matcher));
+            }
+
+            inputs.output_root = Some(normalize(cfg.outputs.root()));
+        }
+
+        Ok(inputs)
+    }
+
+    /// Returns the existing directories that need to be watched, and whether to watch them recursively
+    fn watched_dirs(&self) -> Vec<(PathBuf, RecursiveMode)> {
+        let non_recursive = self
+            .files
+            .iter()
+            .map(|file| file.parent().unwrap_or(Path::new("")))
+            .chain(self.template_dir.iter().map(|(dir, _)| dir.as_path()))
+            .map(|dir| (dir, RecursiveMode::NonRecursive));
+        let recursive = self
+            .globs
+            .iter()
+            .map(|(base, _)| (base.as_path(), RecursiveMode::Recursive));
+
+        non_recursive
+            .chain(recursive)
+            .map(|(dir, mode)| {
+                if dir.as_os_str().is_empty() {
+                    (PathBuf::from("."), mode)
+                } else {
+                    (dir.to_path_buf(), mode)
+                }
+            })
+            .filter(|(dir, _)| dir.is_dir())
+            .fold(Vec::new(), |mut dirs, dir| {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+                dirs
+            })
+    }
+
+    /// Checks if the event is a change to any of the watched inputs
+    fn is_relevant(&self, event: &Event) -> bool {
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            return false;
+        }
+
+        event.paths.iter().any(|path| {
+            let path = match path.strip_prefix(&self.cwd) {
+                Ok(path) => normalize(path),
+                Err(_) if path.is_relative() => normalize(path),
+                Err(_) => return false,
+            };
+            self.is_watched_file(&path)
+        })
+    }
+
+    /// Checks if the given path, relative to the project directory, is one of the inputs
+    ///
+    /// The files written by the build itself are never inputs, even if an input glob matches them,
+    /// as they would trigger another rebuild after every build.
+    fn is_watched_file(&self, path: &Path) -> bool {
+        if matches!(&self.output_root, Some(root) if path.starts_with(root))
+            || path.starts_with(LIVE_OUTPUT_DIR)
+            || path == Path::new(CACHE_FILENAME)
+        {
+            return false;
+        }
+        if self.files.iter().any(|file| file == path) {
+            return true;
+        }
+        if let Some((dir, ext)) = &self.template_dir {
+            if path.parent() == Some(dir) && path.extension().unwrap_or_default() == ext {
+                return true;
+            }
+        }
+        self.globs.iter().any(|(base, matcher)| {
+            path.strip_prefix(base)
+                .map(|relative| matcher.matched(relative, false).is_whitelist())
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Removes the `.` components from the path so that the paths can be compared
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|comp| !matches!(comp, Component::CurDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_inputs_are_watched() {
+        let mut cfg = serde_yaml::to_value(DwwbConfig::default()).unwrap();
+        // an input glob that matches every file in the project, including the ones written by the build
+        cfg["inputs"]["everything"] =
+            serde_yaml::from_str("{ base: ., patterns: ['**/*'] }").unwrap();
+        let cfg: DwwbConfig = serde_yaml::from_value(cfg).unwrap();
+        let inputs = WatchedInputs::new(Some(&cfg)).unwrap();
+
+        for path in [
+            "html/index.html",
+            "html/articles/alice.html",
+            ".dwwb-live/index.html",
+            ".dwwb-live/articles/alice.html",
+            ".dwwb-cache",
+        ] {
+            assert!(!inputs.is_watched_file(Path::new(path)), "{path}");
+        }
+        for path in [
+            "dwwb.yaml",
+            "index.md",
+            "style.css",
+            "articles/alice.md",
+            "articles/people/bob.markdown",
+            "templates/sidebar.html",
+            "notes.txt",
+        ] {
+            assert!(inputs.is_watched_file(Path::new(path)), "{path}");
+        }
+
+        let inputs = WatchedInputs::new(Some(&DwwbConfig::default())).unwrap();
+        for path in ["notes.txt", "articles/alice.txt", "templates/notes.txt"] {
+            assert!(!inputs.is_watched_file(Path::new(path)), "{path}");
+        }
+        assert!(inputs.is_watched_file(Path::new("articles/alice.md")));
+    }
+}